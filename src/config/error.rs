@@ -0,0 +1,54 @@
+use std::error::Error as StdError;
+use std::io::Error as IoError;
+
+use toml::ParserError;
+
+/// Composite error type for the configuration.
+#[derive(Debug)]
+pub enum Error {
+    Io(IoError),
+    Parse(Vec<ParserError>),
+    Invalid(String),
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(ref e) => e.description(),
+            Error::Parse(_) => "unable to parse configuration file",
+            Error::Invalid(ref message) => message.as_str(),
+        }
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl ::std::fmt::Display for Error {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "{}", e),
+            Error::Parse(ref errors) => {
+                let descriptions: Vec<&str> = errors.iter().map(|e| e.desc.as_str()).collect();
+                write!(f, "{}", descriptions.join("; "))
+            }
+            Error::Invalid(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(error: IoError) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl From<Vec<ParserError>> for Error {
+    fn from(errors: Vec<ParserError>) -> Error {
+        Error::Parse(errors)
+    }
+}
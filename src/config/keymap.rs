@@ -0,0 +1,423 @@
+//! Key sequences bound to the commands of the station screen.
+//!
+//! A key is written as the character itself (`"n"`), `C-` or `M-` followed by a
+//! character for the control and alt modifiers (`"C-x"`, `"M-x"`), or the name of
+//! a special key between angle brackets: `<left>`, `<right>`, `<up>`, `<down>`,
+//! `<home>`, `<end>`, `<pageup>`, `<pagedown>`, `<insert>`, `<delete>`,
+//! `<backspace>`, `<enter>`, `<tab>`, `<esc>`, `<space>` and `<f1>` to `<f12>`.
+//! A sequence is written as its keys separated by spaces (`"g g"`, `"C-x C-c"`).
+
+use super::Error;
+
+use toml::{Table, Value};
+
+use ncurses as nc;
+
+use std::collections::BTreeMap;
+
+/// Default keybindings, the first element is the command name and the second
+/// one the key sequence bound to it.
+static DEFAULT_KEYBINDINGS: &'static [(&'static str, &'static str)] = &[
+    ("help", "?"),
+    ("skip", "n"),
+    ("pause", "p"),
+    ("replay", "b"),
+    ("seek_back", "<left>"),
+    ("seek_forward", "<right>"),
+    ("create", "c"),
+    ("rename", "r"),
+    ("add_variety", "a"),
+    ("select", "s"),
+    ("delete", "d"),
+    ("undo", "U"),
+    ("manage", "M"),
+    ("quality", "u"),
+    ("volume_down", "["),
+    ("volume_up", "]"),
+    ("mute", "m"),
+    ("love", "+"),
+    ("ban", "-"),
+    ("command_line", ":"),
+    ("quit", "q"),
+];
+
+/// Names of the special keys, without the angle brackets.
+static KEY_NAMES: &'static [(&'static str, Key)] = &[
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+    ("insert", Key::Insert),
+    ("delete", Key::Delete),
+    ("backspace", Key::Backspace),
+    ("enter", Key::Enter),
+    ("tab", Key::Tab),
+    ("esc", Key::Escape),
+    ("space", Key::Char(' ')),
+];
+
+/// Highest function key that can be bound.
+const MAX_FUNCTION_KEY: u8 = 12;
+
+/// A key press, as read from ncurses.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Char(char),
+    /// A letter with the control modifier.
+    Ctrl(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    Backspace,
+    Enter,
+    Tab,
+    Escape,
+    /// Function key, from 1.
+    F(u8),
+}
+
+impl Key {
+    /// Returns the key for the given ncurses key code, if it can be bound.
+    pub fn from_code(code: i32) -> Option<Self> {
+        Some(match code {
+                 nc::KEY_LEFT => Key::Left,
+                 nc::KEY_RIGHT => Key::Right,
+                 nc::KEY_UP => Key::Up,
+                 nc::KEY_DOWN => Key::Down,
+                 nc::KEY_HOME => Key::Home,
+                 nc::KEY_END => Key::End,
+                 nc::KEY_PPAGE => Key::PageUp,
+                 nc::KEY_NPAGE => Key::PageDown,
+                 nc::KEY_IC => Key::Insert,
+                 nc::KEY_DC => Key::Delete,
+                 nc::KEY_BACKSPACE | 8 | 127 => Key::Backspace,
+                 nc::KEY_ENTER | 10 | 13 => Key::Enter,
+                 9 => Key::Tab,
+                 27 => Key::Escape,
+                 _ if code >= 1 && code <= 26 => Key::Ctrl((b'a' + code as u8 - 1) as char),
+                 _ if code >= 32 && code < 127 => Key::Char(code as u8 as char),
+                 _ if code > nc::KEY_F0 && code <= nc::KEY_F(MAX_FUNCTION_KEY) => {
+                     Key::F((code - nc::KEY_F0) as u8)
+                 }
+                 _ => return None,
+             })
+    }
+
+    /// Returns the name of the key as written in the configuration file.
+    pub fn name(&self) -> String {
+        match *self {
+            Key::Char(c) => {
+                match KEY_NAMES.iter().find(|&&(_, key)| key == *self) {
+                    Some(&(name, _)) => format!("<{}>", name),
+                    None => c.to_string(),
+                }
+            }
+            Key::Ctrl(c) => format!("C-{}", c),
+            Key::F(n) => format!("<f{}>", n),
+            key => {
+                let name = KEY_NAMES.iter()
+                    .find(|&&(_, k)| k == key)
+                    .map(|&(name, _)| name)
+                    .unwrap_or("?");
+                format!("<{}>", name)
+            }
+        }
+    }
+}
+
+/// Result of looking up a key sequence.
+pub enum Binding<'a> {
+    /// No command is bound to the sequence or to any sequence starting with it.
+    None,
+    /// The sequence is the start of bound sequences.
+    Prefix,
+    /// The command bound to the sequence.
+    Command(&'a str),
+}
+
+/// Key sequences bound to commands.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: BTreeMap<Vec<Key>, String>,
+    // Conflicts found when loading the keymap, to report them to the user.
+    conflicts: Vec<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULT_KEYBINDINGS.iter()
+            .map(|&(command, keys)| (parse_sequence(keys).unwrap(), command.to_owned()))
+            .collect();
+        Keymap {
+            bindings: bindings,
+            conflicts: Vec::new(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Builds the keymap from the `[keybindings]` table of the configuration,
+    /// over the default keybindings. The keys bound to a command in the table
+    /// replace its default keys; an empty list unbinds the command.
+    pub fn from_table(table: &Table) -> Result<Self, Error> {
+        let mut keymap = Self::default();
+
+        let mut bindings = Vec::new();
+        for (command, value) in table {
+            if !DEFAULT_KEYBINDINGS.iter().any(|&(c, _)| c == command) {
+                return Err(Error::Invalid(format!("keybindings.{} is not a known command",
+                                                  command)));
+            }
+            let sequences = match *value {
+                Value::String(ref sequence) => vec![sequence.as_str()],
+                Value::Array(ref sequences) if sequences.iter().all(|s| s.as_str().is_some()) => {
+                    sequences.iter().filter_map(Value::as_str).collect()
+                }
+                _ => {
+                    return Err(Error::Invalid(format!("keybindings.{} must be a string or a \
+                                                       list of strings",
+                                                      command)))
+                }
+            };
+            for sequence in sequences {
+                match parse_sequence(sequence) {
+                    Some(keys) => bindings.push((keys, command.as_str())),
+                    None => {
+                        return Err(Error::Invalid(format!("keybindings.{} has an invalid key \
+                                                           sequence \"{}\"",
+                                                          command,
+                                                          sequence)))
+                    }
+                }
+            }
+            let defaults: Vec<Vec<Key>> = keymap.bindings
+                .iter()
+                .filter(|&(_, c)| c == command)
+                .map(|(keys, _)| keys.clone())
+                .collect();
+            for keys in defaults {
+                keymap.bindings.remove(&keys);
+            }
+        }
+
+        for (keys, command) in bindings {
+            keymap.bind(keys, command);
+        }
+        keymap.check_prefixes();
+        Ok(keymap)
+    }
+
+    /// Looks up the given key sequence.
+    pub fn lookup(&self, keys: &[Key]) -> Binding {
+        if let Some(command) = self.bindings.get(keys) {
+            return Binding::Command(command);
+        }
+        if self.bindings.keys().any(|sequence| sequence.starts_with(keys)) {
+            Binding::Prefix
+        } else {
+            Binding::None
+        }
+    }
+
+    /// Returns the names of the key sequences bound to the given command.
+    pub fn keys(&self, command: &str) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|&(_, c)| c == command)
+            .map(|(keys, _)| sequence_name(keys))
+            .collect()
+    }
+
+    /// Returns the conflicts found when loading the keymap.
+    pub fn conflicts(&self) -> &[String] {
+        &self.conflicts
+    }
+
+    /// Binds the given sequence to the given command, replacing (and reporting)
+    /// the command it was bound to.
+    fn bind(&mut self, keys: Vec<Key>, command: &str) {
+        if let Some(previous) = self.bindings.get(&keys) {
+            if previous != command {
+                self.conflicts.push(format!("\"{}\" is bound to both {} and {}, using {}",
+                                            sequence_name(&keys),
+                                            previous,
+                                            command,
+                                            command));
+            }
+        }
+        self.bindings.insert(keys, command.to_owned());
+    }
+
+    /// Reports the sequences that can't be typed because a shorter sequence
+    /// bound to another command is their start.
+    fn check_prefixes(&mut self) {
+        for (keys, command) in &self.bindings {
+            for (other_keys, other_command) in &self.bindings {
+                if other_keys.len() > keys.len() && other_keys.starts_with(keys) {
+                    self.conflicts.push(format!("\"{}\" ({}) can't be used, \"{}\" ({}) is \
+                                                 bound to its start",
+                                                sequence_name(other_keys),
+                                                other_command,
+                                                sequence_name(keys),
+                                                command));
+                }
+            }
+        }
+    }
+}
+
+/// Parses a key sequence as written in the configuration file.
+pub fn parse_sequence(sequence: &str) -> Option<Vec<Key>> {
+    // A lone space is the space key, not an empty sequence.
+    if sequence == " " {
+        return Some(vec![Key::Char(' ')]);
+    }
+
+    let mut keys = Vec::new();
+    for name in sequence.split_whitespace() {
+        // Alt sends escape before the key.
+        let name = if name.starts_with("M-") && name.len() > 2 {
+            keys.push(Key::Escape);
+            &name[2..]
+        } else {
+            name
+        };
+        match parse_key(name) {
+            Some(key) => keys.push(key),
+            None => return None,
+        }
+    }
+    if keys.is_empty() { None } else { Some(keys) }
+}
+
+fn parse_key(name: &str) -> Option<Key> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        // Keys are read a byte at a time, so only ASCII characters can be bound.
+        (Some(c), None) if c.is_ascii() => return Key::from_code(c as i32),
+        (Some(_), None) | (None, _) => return None,
+        _ => (),
+    }
+
+    if name.starts_with("C-") {
+        let mut chars = name[2..].chars();
+        return match (chars.next(), chars.next()) {
+                   // Some of them are read as other keys, e.g. C-i as tab.
+                   (Some(c), None) if c.is_ascii_alphabetic() => {
+                       Key::from_code((c.to_ascii_lowercase() as u8 - b'a' + 1) as i32)
+                   }
+                   _ => None,
+               };
+    }
+
+    if name.starts_with('<') && name.ends_with('>') {
+        let name = name[1..name.len() - 1].to_lowercase();
+        if let Some(&(_, key)) = KEY_NAMES.iter().find(|&&(n, _)| n == name) {
+            return Some(key);
+        }
+        if name.starts_with('f') {
+            if let Ok(n) = name[1..].parse::<u8>() {
+                if n >= 1 && n <= MAX_FUNCTION_KEY {
+                    return Some(Key::F(n));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Returns the name of the given sequence as written in the configuration file.
+pub fn sequence_name(keys: &[Key]) -> String {
+    let mut names: Vec<String> = Vec::with_capacity(keys.len());
+    let mut alt = false;
+    for key in keys {
+        if alt {
+            names.push(format!("M-{}", key.name()));
+            alt = false;
+        } else if *key == Key::Escape {
+            alt = true;
+        } else {
+            names.push(key.name());
+        }
+    }
+    if alt {
+        names.push(Key::Escape.name());
+    }
+    names.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Config;
+
+    fn keymap(toml: &str) -> Keymap {
+        Config::from_toml(toml).unwrap().keymap().clone()
+    }
+
+    #[test]
+    fn parses_sequences() {
+        assert_eq!(parse_sequence("n"), Some(vec![Key::Char('n')]));
+        assert_eq!(parse_sequence("C-x C-c"), Some(vec![Key::Ctrl('x'), Key::Ctrl('c')]));
+        assert_eq!(parse_sequence("M-x"), Some(vec![Key::Escape, Key::Char('x')]));
+        assert_eq!(parse_sequence("g  g"), Some(vec![Key::Char('g'), Key::Char('g')]));
+        assert_eq!(parse_sequence(" "), Some(vec![Key::Char(' ')]));
+        assert_eq!(parse_sequence("<Left> <f12>"), Some(vec![Key::Left, Key::F(12)]));
+        // Read as tab by the terminal.
+        assert_eq!(parse_sequence("C-i"), Some(vec![Key::Tab]));
+    }
+
+    #[test]
+    fn rejects_invalid_sequences() {
+        for sequence in &["", "  ", "nn", "C-1", "C-", "<f13>", "<nope>", "é", "M-é"] {
+            assert_eq!(parse_sequence(sequence), None, "{:?}", sequence);
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for sequence in &["n", "?", "C-x C-c", "M-x", "g g", "<space>", "<left>", "<f5>",
+                          "<esc>", "M-<enter>"] {
+            let keys = parse_sequence(sequence).unwrap();
+            assert_eq!(sequence_name(&keys), *sequence);
+        }
+    }
+
+    #[test]
+    fn reports_duplicate_bindings() {
+        let keymap = keymap("[keybindings]\npause = \"n\"");
+        assert_eq!(keymap.conflicts(),
+                   &["\"n\" is bound to both skip and pause, using pause".to_owned()]);
+        assert_eq!(keymap.keys("pause"), vec!["n".to_owned()]);
+        assert!(keymap.keys("skip").is_empty());
+    }
+
+    #[test]
+    fn reports_prefix_bindings() {
+        let keymap = keymap("[keybindings]\nhelp = \"g\"\nquit = \"g g\"");
+        assert_eq!(keymap.conflicts(),
+                   &["\"g g\" (quit) can't be used, \"g\" (help) is bound to its start"
+                         .to_owned()]);
+    }
+
+    #[test]
+    fn rejects_non_ascii_keys() {
+        match Config::from_toml("[keybindings]\nskip = \"é\"") {
+            Err(Error::Invalid(message)) => {
+                assert_eq!(message, "keybindings.skip has an invalid key sequence \"é\"")
+            }
+            _ => panic!("expected an invalid key sequence"),
+        }
+    }
+}
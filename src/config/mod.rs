@@ -0,0 +1,781 @@
+//! User settings loaded from a TOML file at startup.
+//!
+//! The file is looked up at `$XDG_CONFIG_HOME/dobro/config.toml`, falling back to
+//! `~/.config/dobro/config.toml`. Every setting is optional; missing ones take the
+//! defaults below. The `DOBRO_EMAIL` and `DOBRO_PASSWORD` environment variables take
+//! precedence over the login settings in the file. A minimal file looks like:
+//!
+//! ```toml
+//! [login]
+//! email = "user@example.com"
+//! # Either the password itself, or a command that prints it.
+//! password = "secret"
+//! password_command = "pass show pandora"
+//!
+//! [player]
+//! default_station = "Daft Punk Radio"
+//! audio_quality = "high"
+//! playlist_refill = 2
+//!
+//! [output]
+//! sink = "ao"
+//! driver = "pulse"
+//!
+//! [output.options]
+//! server = "localhost"
+//!
+//! [cache]
+//! enabled = true
+//! size = 512
+//!
+//! [keybindings]
+//! skip = ["n", "<f9>"]
+//! pause = "<space>"
+//! quit = "C-x C-c"
+//!
+//! [ui]
+//! help_hint = true
+//! time_format = "both"
+//! progress_bar = true
+//! # Ask to type the station name, instead of "y", to delete a station.
+//! confirm_by_name = false
+//! ```
+
+mod error;
+mod keymap;
+mod saved;
+
+pub use self::error::Error;
+pub use self::keymap::{Binding, Key, Keymap};
+pub use self::saved::SavedState;
+
+use toml::{Table, Value};
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// libao drivers that write to a file.
+static FILE_DRIVERS: &'static [&'static str] = &["au", "raw", "wav"];
+
+/// Quality of the audio streams requested to Pandora.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AudioQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl AudioQuality {
+    /// Parses the quality from its name in the configuration file.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "low" => Some(AudioQuality::Low),
+            "medium" => Some(AudioQuality::Medium),
+            "high" => Some(AudioQuality::High),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the quality as used in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            AudioQuality::Low => "low",
+            AudioQuality::Medium => "medium",
+            AudioQuality::High => "high",
+        }
+    }
+
+    /// Returns the next quality, wrapping around after the highest one.
+    pub fn next(&self) -> Self {
+        match *self {
+            AudioQuality::Low => AudioQuality::Medium,
+            AudioQuality::Medium => AudioQuality::High,
+            AudioQuality::High => AudioQuality::Low,
+        }
+    }
+
+    /// Returns the order in which qualities are tried when this one is preferred:
+    /// itself, then the lower ones, then the higher ones.
+    pub fn fallbacks(&self) -> [AudioQuality; 3] {
+        match *self {
+            AudioQuality::Low => [AudioQuality::Low, AudioQuality::Medium, AudioQuality::High],
+            AudioQuality::Medium => [AudioQuality::Medium, AudioQuality::Low, AudioQuality::High],
+            AudioQuality::High => [AudioQuality::High, AudioQuality::Medium, AudioQuality::Low],
+        }
+    }
+}
+
+/// How the progress of the current track is shown.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TimeFormat {
+    /// Elapsed time and track length.
+    Elapsed,
+    /// Remaining time and track length.
+    Remaining,
+    /// Elapsed and remaining time.
+    Both,
+}
+
+impl TimeFormat {
+    /// Parses the format from its name in the configuration file.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "elapsed" => Some(TimeFormat::Elapsed),
+            "remaining" => Some(TimeFormat::Remaining),
+            "both" => Some(TimeFormat::Both),
+            _ => None,
+        }
+    }
+}
+
+/// Where the decoded audio goes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// A libao device, with the given driver (the default one if `None`) and
+    /// driver options.
+    Ao {
+        driver: Option<String>,
+        options: BTreeMap<String, String>,
+    },
+    /// A WAV file at the given path.
+    Wav(PathBuf),
+    /// A raw PCM file at the given path.
+    Raw(PathBuf),
+    /// Nowhere, the audio is decoded and discarded.
+    Null,
+    /// Raw PCM to the standard output.
+    Stdout,
+}
+
+/// Typed user settings.
+#[derive(Debug, Clone)]
+pub struct Config {
+    // Login.
+    email: Option<String>,
+    password: Option<String>,
+    password_command: Option<String>,
+
+    // Player.
+    default_station: Option<String>,
+    audio_quality: AudioQuality,
+    playlist_refill: usize,
+    replay_gain: bool,
+    replay_gain_preamp: f32,
+    crossfade: usize,
+
+    // Output.
+    output: Output,
+
+    // Track cache, limited to a size in bytes.
+    cache: bool,
+    cache_dir: Option<PathBuf>,
+    cache_size: u64,
+
+    // Key sequences to command names.
+    keymap: Keymap,
+
+    // User interface.
+    help_hint: bool,
+    time_format: TimeFormat,
+    progress_bar: bool,
+    confirm_by_name: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            email: None,
+            password: None,
+            password_command: None,
+
+            default_station: None,
+            audio_quality: AudioQuality::High,
+            playlist_refill: 2,
+            replay_gain: true,
+            replay_gain_preamp: 0.0,
+            crossfade: 0,
+
+            output: Output::Ao {
+                driver: None,
+                options: BTreeMap::new(),
+            },
+
+            cache: false,
+            cache_dir: None,
+            cache_size: 512 * 1024 * 1024,
+
+            keymap: Keymap::default(),
+
+            help_hint: true,
+            time_format: TimeFormat::Both,
+            progress_bar: true,
+            confirm_by_name: false,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration from the given path, or from the default path
+    /// if none is given. A missing file at the default path is not an error,
+    /// the default configuration is returned instead.
+    ///
+    /// Login settings from the environment override the ones in the file.
+    pub fn load(path: Option<&Path>) -> Result<Self, Error> {
+        let config = match path {
+            Some(path) => try!(Self::from_file(path)),
+            None => {
+                match default_path() {
+                    Some(ref path) if path.exists() => try!(Self::from_file(path)),
+                    _ => Self::default(),
+                }
+            }
+        };
+        Ok(config.with_env())
+    }
+
+    /// Reads and parses the configuration file at the given path.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let mut file = try!(File::open(path));
+        let mut contents = String::new();
+        try!(file.read_to_string(&mut contents));
+        Self::from_toml(&contents)
+    }
+
+    /// Parses the configuration from a TOML string.
+    pub fn from_toml(toml: &str) -> Result<Self, Error> {
+        let root = try!(toml.parse::<Value>());
+        let mut config = Self::default();
+
+        if let Some(login) = try!(table(&root, "login")) {
+            config.email = try!(string(login, "login", "email"));
+            config.password = try!(string(login, "login", "password"));
+            config.password_command = try!(string(login, "login", "password_command"));
+        }
+
+        if let Some(player) = try!(table(&root, "player")) {
+            config.default_station = try!(string(player, "player", "default_station"));
+            if let Some(name) = try!(string(player, "player", "audio_quality")) {
+                config.audio_quality = match AudioQuality::from_name(&name) {
+                    Some(quality) => quality,
+                    None => {
+                        return Err(Error::Invalid(format!("player.audio_quality must be one \
+                                                           of low, medium or high, found \
+                                                           \"{}\"",
+                                                          name)))
+                    }
+                };
+            }
+            if let Some(refill) = try!(natural(player, "player", "playlist_refill")) {
+                // With 0 the tracklist would never be refilled.
+                if refill == 0 {
+                    return Err(Error::Invalid("player.playlist_refill must be at least 1"
+                        .to_owned()));
+                }
+                config.playlist_refill = refill;
+            }
+            if let Some(replay_gain) = try!(boolean(player, "player", "replay_gain")) {
+                config.replay_gain = replay_gain;
+            }
+            if let Some(preamp) = try!(number(player, "player", "replay_gain_preamp")) {
+                config.replay_gain_preamp = preamp as f32;
+            }
+            if let Some(crossfade) = try!(natural(player, "player", "crossfade")) {
+                config.crossfade = crossfade;
+            }
+        }
+
+        if let Some(output) = try!(table(&root, "output")) {
+            let path = try!(string(output, "output", "path")).map(PathBuf::from);
+            let driver = try!(string(output, "output", "driver"));
+            let mut options = BTreeMap::new();
+            if let Some(ao_options) = try!(table(&root, "output.options")) {
+                for (key, value) in ao_options {
+                    match value.as_str() {
+                        Some(value) => options.insert(key.clone(), value.to_owned()),
+                        None => {
+                            return Err(Error::Invalid(format!("output.options.{} must be a \
+                                                               string",
+                                                              key)))
+                        }
+                    };
+                }
+            }
+
+            let sink = try!(string(output, "output", "sink")).unwrap_or("ao".to_owned());
+            config.output = match (sink.to_lowercase().as_str(), path) {
+                // Only live devices can be opened, file drivers fail when played.
+                ("ao", _) if driver.as_ref().map_or(false, |d| is_file_driver(d)) => {
+                    return Err(Error::Invalid(format!("output.driver \"{}\" writes to a file, \
+                                                       which the ao sink can't do; use sink = \
+                                                       \"wav\" or \"raw\" with output.path \
+                                                       instead",
+                                                      driver.unwrap_or_default())))
+                }
+                ("ao", _) => {
+                    Output::Ao {
+                        driver: driver,
+                        options: options,
+                    }
+                }
+                ("wav", Some(path)) => Output::Wav(path),
+                ("raw", Some(path)) => Output::Raw(path),
+                ("wav", None) | ("raw", None) => {
+                    return Err(Error::Invalid(format!("output.path is required for the \
+                                                       {} sink",
+                                                      sink)))
+                }
+                ("null", _) => Output::Null,
+                ("stdout", _) => Output::Stdout,
+                _ => {
+                    return Err(Error::Invalid(format!("output.sink must be one of ao, \
+                                                       wav, raw, null or stdout, found \
+                                                       \"{}\"",
+                                                      sink)))
+                }
+            };
+        }
+
+        if let Some(cache) = try!(table(&root, "cache")) {
+            if let Some(enabled) = try!(boolean(cache, "cache", "enabled")) {
+                config.cache = enabled;
+            }
+            config.cache_dir = try!(string(cache, "cache", "dir")).map(PathBuf::from);
+            if let Some(size) = try!(natural(cache, "cache", "size")) {
+                config.cache_size = match (size as u64).checked_mul(1024 * 1024) {
+                    Some(bytes) => bytes,
+                    None => {
+                        return Err(Error::Invalid(format!("cache.size must be at most {} MiB",
+                                                          u64::max_value() / (1024 * 1024))))
+                    }
+                };
+            }
+        }
+
+        if let Some(keybindings) = try!(table(&root, "keybindings")) {
+            config.keymap = try!(Keymap::from_table(keybindings));
+        }
+
+        if let Some(ui) = try!(table(&root, "ui")) {
+            if let Some(help_hint) = try!(boolean(ui, "ui", "help_hint")) {
+                config.help_hint = help_hint;
+            }
+            if let Some(name) = try!(string(ui, "ui", "time_format")) {
+                config.time_format = match TimeFormat::from_name(&name) {
+                    Some(format) => format,
+                    None => {
+                        return Err(Error::Invalid(format!("ui.time_format must be one of \
+                                                           elapsed, remaining or both, found \
+                                                           \"{}\"",
+                                                          name)))
+                    }
+                };
+            }
+            if let Some(progress_bar) = try!(boolean(ui, "ui", "progress_bar")) {
+                config.progress_bar = progress_bar;
+            }
+            if let Some(confirm_by_name) = try!(boolean(ui, "ui", "confirm_by_name")) {
+                config.confirm_by_name = confirm_by_name;
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Overrides the login settings with the `DOBRO_EMAIL` and `DOBRO_PASSWORD`
+    /// environment variables, if set.
+    fn with_env(mut self) -> Self {
+        if let Some(email) = env_var("DOBRO_EMAIL") {
+            self.email = Some(email);
+        }
+        if let Some(password) = env_var("DOBRO_PASSWORD") {
+            self.password = Some(password);
+        }
+        self
+    }
+
+    /// Returns the email used for login, if any.
+    pub fn email(&self) -> Option<&str> {
+        self.email.as_ref().map(|s| s.as_str())
+    }
+
+    /// Returns the password used for login, if any.
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_ref().map(|s| s.as_str())
+    }
+
+    /// Runs the password command (if any) and returns the first line of its
+    /// output as the password. The command gets the standard input and error of
+    /// the terminal, so it can prompt for a passphrase.
+    pub fn command_password(&self) -> Option<Result<String, Error>> {
+        self.password_command.as_ref().map(|command| {
+            let output = try!(Command::new("sh")
+                                  .arg("-c")
+                                  .arg(command)
+                                  .stdin(Stdio::inherit())
+                                  .stderr(Stdio::inherit())
+                                  .output());
+            if !output.status.success() {
+                return Err(Error::Invalid(format!("password command exited with {}",
+                                                  output.status)));
+            }
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            match stdout.lines().next() {
+                Some(password) if !password.is_empty() => Ok(password.to_owned()),
+                _ => Err(Error::Invalid("password command printed nothing".to_owned())),
+            }
+        })
+    }
+
+    /// Returns the name of the station to play at startup, if any.
+    pub fn default_station(&self) -> Option<&str> {
+        self.default_station.as_ref().map(|s| s.as_str())
+    }
+
+    /// Returns the preferred audio quality.
+    pub fn audio_quality(&self) -> AudioQuality {
+        self.audio_quality
+    }
+
+    /// Returns the number of queued tracks under which the next playlist is
+    /// fetched in the background.
+    pub fn playlist_refill(&self) -> usize {
+        self.playlist_refill
+    }
+
+    /// Returns the gain in dB added to the replay gain of every track, or `None`
+    /// if replay gain is disabled.
+    pub fn replay_gain(&self) -> Option<f32> {
+        if self.replay_gain {
+            Some(self.replay_gain_preamp)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the seconds the end of a track is mixed with the start of the
+    /// next one, 0 if tracks shouldn't be crossfaded.
+    pub fn crossfade(&self) -> usize {
+        self.crossfade
+    }
+
+    /// Returns where the decoded audio goes.
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Returns the directory for the track cache, or `None` if the cache is
+    /// disabled.
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        if !self.cache {
+            return None;
+        }
+        match self.cache_dir {
+            Some(ref dir) => Some(dir.clone()),
+            None => default_cache_dir(),
+        }
+    }
+
+    /// Returns the size limit of the track cache, in bytes.
+    pub fn cache_size(&self) -> u64 {
+        self.cache_size
+    }
+
+    /// Returns the key sequences bound to the commands.
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    /// Returns true if the hint for the help screen should be shown.
+    pub fn help_hint(&self) -> bool {
+        self.help_hint
+    }
+
+    /// Returns how the progress of the current track is shown.
+    pub fn time_format(&self) -> TimeFormat {
+        self.time_format
+    }
+
+    /// Returns true if the progress of the current track is shown as a bar.
+    pub fn progress_bar(&self) -> bool {
+        self.progress_bar
+    }
+
+    /// Returns true if the station name must be typed to delete a station.
+    pub fn confirm_by_name(&self) -> bool {
+        self.confirm_by_name
+    }
+}
+
+/// Returns the default location of the configuration file.
+pub fn default_path() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("config.toml"))
+}
+
+/// Returns the default directory of the track cache.
+fn default_cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// Returns the dobro directory in the XDG base directory set in the given
+/// environment variable, or in `fallback` under the home directory if it is
+/// not set.
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    let base = match env::var_os(var) {
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            match env::var_os("HOME") {
+                Some(home) => Path::new(&home).join(fallback),
+                None => return None,
+            }
+        }
+    };
+    Some(base.join("dobro"))
+}
+
+/// Returns true if the given libao driver writes to a file instead of a device.
+fn is_file_driver(driver: &str) -> bool {
+    FILE_DRIVERS.contains(&driver.to_lowercase().as_str())
+}
+
+/// Returns the value of the given environment variable, `None` if it is
+/// not set or empty.
+fn env_var(name: &str) -> Option<String> {
+    match env::var(name) {
+        Ok(ref value) if !value.is_empty() => Some(value.clone()),
+        _ => None,
+    }
+}
+
+// ----------------
+// Value helpers
+// ----------------
+
+fn table<'a>(root: &'a Value, name: &str) -> Result<Option<&'a Table>, Error> {
+    match root.lookup(name) {
+        Some(&Value::Table(ref table)) => Ok(Some(table)),
+        Some(_) => Err(Error::Invalid(format!("[{}] must be a table", name))),
+        None => Ok(None),
+    }
+}
+
+fn string(table: &Table, section: &str, key: &str) -> Result<Option<String>, Error> {
+    match table.get(key) {
+        Some(&Value::String(ref value)) => Ok(Some(value.clone())),
+        Some(_) => Err(Error::Invalid(format!("{}.{} must be a string", section, key))),
+        None => Ok(None),
+    }
+}
+
+fn natural(table: &Table, section: &str, key: &str) -> Result<Option<usize>, Error> {
+    match table.get(key) {
+        Some(&Value::Integer(value)) if value >= 0 => Ok(Some(value as usize)),
+        Some(_) => {
+            Err(Error::Invalid(format!("{}.{} must be a non-negative integer", section, key)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn number(table: &Table, section: &str, key: &str) -> Result<Option<f64>, Error> {
+    match table.get(key) {
+        Some(&Value::Float(value)) => Ok(Some(value)),
+        Some(&Value::Integer(value)) => Ok(Some(value as f64)),
+        Some(_) => Err(Error::Invalid(format!("{}.{} must be a number", section, key))),
+        None => Ok(None),
+    }
+}
+
+fn boolean(table: &Table, section: &str, key: &str) -> Result<Option<bool>, Error> {
+    match table.get(key) {
+        Some(&Value::Boolean(value)) => Ok(Some(value)),
+        Some(_) => Err(Error::Invalid(format!("{}.{} must be a boolean", section, key))),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the message of the validation error for the given configuration.
+    fn invalid(toml: &str) -> String {
+        match Config::from_toml(toml) {
+            Err(Error::Invalid(message)) => message,
+            Err(e) => panic!("expected a validation error, got {}", e),
+            Ok(_) => panic!("expected a validation error for {}", toml),
+        }
+    }
+
+    #[test]
+    fn empty_file_has_defaults() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.email(), None);
+        assert_eq!(config.password(), None);
+        assert!(config.command_password().is_none());
+        assert_eq!(config.default_station(), None);
+        assert_eq!(config.audio_quality(), AudioQuality::High);
+        assert_eq!(config.playlist_refill(), 2);
+        assert_eq!(config.replay_gain(), Some(0.0));
+        assert_eq!(config.crossfade(), 0);
+        assert_eq!(*config.output(),
+                   Output::Ao {
+                       driver: None,
+                       options: BTreeMap::new(),
+                   });
+        assert_eq!(config.cache_dir(), None);
+        assert_eq!(config.cache_size(), 512 * 1024 * 1024);
+        assert!(config.help_hint());
+        assert_eq!(config.time_format(), TimeFormat::Both);
+        assert!(config.progress_bar());
+        assert!(!config.confirm_by_name());
+    }
+
+    #[test]
+    fn parses_every_section() {
+        let config = Config::from_toml(r#"
+            [login]
+            email = "user@example.com"
+            password = "secret"
+
+            [player]
+            default_station = "Daft Punk Radio"
+            audio_quality = "Low"
+            playlist_refill = 4
+            replay_gain = true
+            replay_gain_preamp = -3
+            crossfade = 5
+
+            [output]
+            sink = "ao"
+            driver = "pulse"
+
+            [output.options]
+            server = "localhost"
+
+            [cache]
+            enabled = true
+            dir = "/tmp/dobro"
+            size = 64
+
+            [ui]
+            help_hint = false
+            time_format = "remaining"
+            progress_bar = false
+            confirm_by_name = true
+        "#)
+            .unwrap();
+        assert_eq!(config.email(), Some("user@example.com"));
+        assert_eq!(config.password(), Some("secret"));
+        assert_eq!(config.default_station(), Some("Daft Punk Radio"));
+        assert_eq!(config.audio_quality(), AudioQuality::Low);
+        assert_eq!(config.playlist_refill(), 4);
+        assert_eq!(config.replay_gain(), Some(-3.0));
+        assert_eq!(config.crossfade(), 5);
+        let mut options = BTreeMap::new();
+        options.insert("server".to_owned(), "localhost".to_owned());
+        assert_eq!(*config.output(),
+                   Output::Ao {
+                       driver: Some("pulse".to_owned()),
+                       options: options,
+                   });
+        assert_eq!(config.cache_dir(), Some(PathBuf::from("/tmp/dobro")));
+        assert_eq!(config.cache_size(), 64 * 1024 * 1024);
+        assert!(!config.help_hint());
+        assert_eq!(config.time_format(), TimeFormat::Remaining);
+        assert!(!config.progress_bar());
+        assert!(config.confirm_by_name());
+    }
+
+    #[test]
+    fn parses_file_sinks() {
+        let config = Config::from_toml("[output]\nsink = \"wav\"\npath = \"out.wav\"").unwrap();
+        assert_eq!(*config.output(), Output::Wav(PathBuf::from("out.wav")));
+        let config = Config::from_toml("[output]\nsink = \"raw\"\npath = \"out.pcm\"").unwrap();
+        assert_eq!(*config.output(), Output::Raw(PathBuf::from("out.pcm")));
+        let config = Config::from_toml("[output]\nsink = \"null\"").unwrap();
+        assert_eq!(*config.output(), Output::Null);
+        let config = Config::from_toml("[output]\nsink = \"stdout\"").unwrap();
+        assert_eq!(*config.output(), Output::Stdout);
+    }
+
+    #[test]
+    fn disabled_replay_gain() {
+        let config = Config::from_toml("[player]\nreplay_gain = false").unwrap();
+        assert_eq!(config.replay_gain(), None);
+    }
+
+    #[test]
+    fn rejects_invalid_toml() {
+        match Config::from_toml("[player") {
+            Err(Error::Parse(_)) => (),
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn rejects_sections_that_are_not_tables() {
+        assert_eq!(invalid("player = 1"), "[player] must be a table");
+    }
+
+    #[test]
+    fn rejects_values_of_the_wrong_type() {
+        assert_eq!(invalid("[login]\nemail = 1"), "login.email must be a string");
+        assert_eq!(invalid("[player]\nplaylist_refill = -1"),
+                   "player.playlist_refill must be a non-negative integer");
+        assert_eq!(invalid("[player]\nplaylist_refill = 0"),
+                   "player.playlist_refill must be at least 1");
+        assert_eq!(invalid("[player]\ncrossfade = \"5\""),
+                   "player.crossfade must be a non-negative integer");
+        assert_eq!(invalid("[player]\nreplay_gain = 1"),
+                   "player.replay_gain must be a boolean");
+        assert_eq!(invalid("[player]\nreplay_gain_preamp = \"-3\""),
+                   "player.replay_gain_preamp must be a number");
+        assert_eq!(invalid("[cache]\nsize = 1.5"),
+                   "cache.size must be a non-negative integer");
+        assert_eq!(invalid("[ui]\nprogress_bar = \"yes\""),
+                   "ui.progress_bar must be a boolean");
+    }
+
+    #[test]
+    fn rejects_unknown_audio_quality() {
+        assert_eq!(invalid("[player]\naudio_quality = \"best\""),
+                   "player.audio_quality must be one of low, medium or high, found \"best\"");
+    }
+
+    #[test]
+    fn rejects_unknown_time_format() {
+        assert_eq!(invalid("[ui]\ntime_format = \"total\""),
+                   "ui.time_format must be one of elapsed, remaining or both, found \"total\"");
+    }
+
+    #[test]
+    fn rejects_invalid_output() {
+        assert_eq!(invalid("[output]\nsink = \"alsa\""),
+                   "output.sink must be one of ao, wav, raw, null or stdout, found \"alsa\"");
+        assert_eq!(invalid("[output]\nsink = \"wav\""),
+                   "output.path is required for the wav sink");
+        assert_eq!(invalid("[output]\nsink = \"raw\""),
+                   "output.path is required for the raw sink");
+        assert_eq!(invalid("[output]\n[output.options]\nserver = 1"),
+                   "output.options.server must be a string");
+        assert_eq!(invalid("[output]\ndriver = \"wav\""),
+                   "output.driver \"wav\" writes to a file, which the ao sink can't do; use \
+                    sink = \"wav\" or \"raw\" with output.path instead");
+    }
+
+    #[test]
+    fn rejects_unknown_keybinding_command() {
+        assert_eq!(invalid("[keybindings]\nfly = \"f\""),
+                   "keybindings.fly is not a known command");
+    }
+
+    #[test]
+    fn rejects_cache_size_that_overflows() {
+        assert_eq!(invalid("[cache]\nsize = 9223372036854775807"),
+                   "cache.size must be at most 17592186044415 MiB");
+    }
+}
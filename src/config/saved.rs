@@ -0,0 +1,123 @@
+//! Settings changed from within dobro that are kept between sessions. They
+//! are saved apart from the configuration file, which is never written.
+
+use super::{xdg_dir, Error};
+
+use toml::{Table, Value};
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// State saved when dobro quits and restored on the next start.
+#[derive(Debug, Clone)]
+pub struct SavedState {
+    volume: u8,
+    muted: bool,
+    // Station id to the last time it was played, in seconds since the Unix epoch.
+    played: BTreeMap<String, u64>,
+}
+
+impl Default for SavedState {
+    fn default() -> Self {
+        SavedState {
+            volume: 100,
+            muted: false,
+            played: BTreeMap::new(),
+        }
+    }
+}
+
+impl SavedState {
+    /// Loads the saved state from the default path. A missing or invalid file
+    /// gives the default state, since there's nothing the user can do about it.
+    pub fn load() -> Self {
+        default_path()
+            .and_then(|path| Self::from_file(&path).ok())
+            .unwrap_or_default()
+    }
+
+    /// Reads and parses the saved state at the given path.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let mut file = try!(File::open(path));
+        let mut contents = String::new();
+        try!(file.read_to_string(&mut contents));
+
+        let root = try!(contents.parse::<Value>());
+        let mut saved = Self::default();
+        if let Some(volume) = root.lookup("player.volume").and_then(Value::as_integer) {
+            saved.volume = if volume < 0 { 0 } else if volume > 100 { 100 } else { volume as u8 };
+        }
+        if let Some(muted) = root.lookup("player.muted").and_then(Value::as_bool) {
+            saved.muted = muted;
+        }
+        if let Some(played) = root.lookup("played").and_then(Value::as_table) {
+            for (station, time) in played {
+                if let Some(time) = time.as_integer() {
+                    saved.played.insert(station.clone(), time.max(0) as u64);
+                }
+            }
+        }
+        Ok(saved)
+    }
+
+    /// Writes the state to the default path, creating its directory if needed.
+    pub fn save(&self) -> Result<(), Error> {
+        let path = match default_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            try!(fs::create_dir_all(dir));
+        }
+
+        let mut player = Table::new();
+        player.insert("volume".to_owned(), Value::Integer(self.volume as i64));
+        player.insert("muted".to_owned(), Value::Boolean(self.muted));
+        let played = self.played
+            .iter()
+            .map(|(station, &time)| (station.clone(), Value::Integer(time as i64)))
+            .collect();
+        let mut root = Table::new();
+        root.insert("player".to_owned(), Value::Table(player));
+        root.insert("played".to_owned(), Value::Table(played));
+
+        let mut file = try!(File::create(&path));
+        try!(write!(file, "{}", Value::Table(root)));
+        Ok(())
+    }
+
+    /// Returns the saved volume, from 0 to 100.
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, volume: u8) {
+        self.volume = volume;
+    }
+
+    /// Returns true if the player was muted.
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Returns the last time the station with the given id was played, in
+    /// seconds since the Unix epoch.
+    pub fn played(&self, station_id: &str) -> Option<u64> {
+        self.played.get(station_id).cloned()
+    }
+
+    pub fn set_played(&mut self, station_id: &str, time: u64) {
+        self.played.insert(station_id.to_owned(), time);
+    }
+}
+
+/// Returns the default location of the saved state.
+pub fn default_path() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join("state.toml"))
+}
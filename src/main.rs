@@ -1,35 +1,65 @@
 //! This example asks for user login information, shows the available stations, and lets
 //! the user select which station to play.
-//!
-//! **Becareful**, this example is still too simple. It doesn't handle reconnection
-//! to pandora when credentials expire.
 
+extern crate libc;
 extern crate ncurses;
 
 extern crate ao_rs as ao;
 extern crate earwax;
+extern crate hyper;
 extern crate pandora;
+extern crate serde_json;
+extern crate toml;
 
+mod config;
 mod player;
 mod screens;
+mod search;
+mod stations;
 mod ui;
 mod state;
 
 use ncurses as nc;
 
-use pandora::Pandora;
-
+use config::{Config, Output, SavedState};
 use player::Player;
 use state::Automaton;
 use screens::StationScreen;
+use stations::DeletedStation;
+
+use pandora::Pandora;
 
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
 use std::sync::Arc;
 
 use ui::*;
 
+/// Time to wait for the rest of an escape sequence, in milliseconds.
+const ESCAPE_DELAY: i32 = 25;
+
 fn main() {
-    nc::initscr();
+    let config = config_path().and_then(|path| Config::load(path.as_ref().map(|p| p.as_path())));
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            writeln!(io::stderr(), "Unable to load configuration: {}", e).unwrap();
+            process::exit(1);
+        }
+    };
+
+    // The password command runs before ncurses takes over the terminal, it
+    // might need it (e.g. for a pinentry program).
+    let command_password = match config.password() {
+        Some(_) => None,
+        None => config.command_password(),
+    };
+
+    init_screen(&config);
     nc::scrollok(nc::stdscr(), true);
+    nc::cbreak();
     nc::noecho();
 
     nc::attron(nc::A_BOLD());
@@ -37,15 +67,24 @@ fn main() {
     nc::printw("\nPlease login below");
     nc::attroff(nc::A_BOLD());
 
-    nc::attron(nc::A_BOLD());
-    nc::printw("\nEmail: ");
-    nc::attroff(nc::A_BOLD());
-    let email = getstring();
+    let email = match config.email() {
+        Some(email) => email.to_owned(),
+        None => prompt("Email: ", false),
+    };
 
-    nc::attron(nc::A_BOLD());
-    nc::printw("\nPassword: ");
-    nc::attroff(nc::A_BOLD());
-    let password = getsecretstring();
+    let password = match config.password() {
+        Some(password) => password.to_owned(),
+        None => {
+            match command_password {
+                Some(Ok(password)) => password,
+                Some(Err(e)) => {
+                    nc::printw(&format!("\nUnable to get password from command: {}", e));
+                    prompt("Password: ", true)
+                }
+                None => prompt("Password: ", true),
+            }
+        }
+    };
 
     nc::printw("\nLogging in... ");
     nc::refresh();
@@ -53,14 +92,33 @@ fn main() {
     match Pandora::new(&email.trim(), &password.trim()) {
         Ok(pandora) => {
             nc::printw("Done\n");
-            let mut dobro = Dobro::new(pandora);
+            nc::clear();
+            let mut dobro = Dobro::new(pandora, config, SavedState::load());
+            let config = dobro.config().clone();
+            for conflict in config.keymap().conflicts() {
+                dobro.ui().print(&format!("Keybinding conflict: {}\n", conflict));
+            }
+            let volume = dobro.saved().volume();
+            dobro.player_mut().set_volume(volume);
+            if dobro.saved().is_muted() {
+                dobro.player_mut().toggle_mute();
+            }
+
             let mut automaton = Automaton::new(StationScreen::new());
 
             automaton.start(&mut dobro);
 
             while automaton.is_running() {
                 automaton.update(&mut dobro);
-                nc::refresh();
+                dobro.draw();
+            }
+
+            let volume = dobro.player().volume();
+            let muted = dobro.player().is_muted();
+            dobro.saved_mut().set_volume(volume);
+            dobro.saved_mut().set_muted(muted);
+            if let Err(e) = dobro.saved().save() {
+                dobro.ui().print(&format!("Unable to save the player state: {}\n", e));
             }
         }
         Err(_) => {
@@ -74,22 +132,92 @@ fn main() {
     nc::endwin();
 }
 
+/// Initializes ncurses. When the audio goes to the standard output, the screen
+/// is drawn on the controlling terminal instead.
+fn init_screen(config: &Config) {
+    // Multi-byte characters are read and drawn in the user locale.
+    nc::setlocale(nc::LcCategory::all, "");
+    // Escape cancels prompts and menus, without waiting for a longer sequence.
+    nc::set_escdelay(ESCAPE_DELAY);
+    if *config.output() == Output::Stdout {
+        let tty = unsafe {
+            libc::fopen(b"/dev/tty\0".as_ptr() as *const libc::c_char,
+                        b"r+\0".as_ptr() as *const libc::c_char)
+        };
+        if !tty.is_null() {
+            nc::newterm(None, tty, tty);
+            return;
+        }
+    }
+    nc::initscr();
+}
+
+/// Prompts the user for a line of input, `secret` hides what is typed.
+fn prompt(message: &str, secret: bool) -> String {
+    nc::attron(nc::A_BOLD());
+    nc::printw(&format!("\n{}", message));
+    nc::attroff(nc::A_BOLD());
+    let mut editor = if secret {
+        LineEditor::masked()
+    } else {
+        LineEditor::new()
+    };
+    editor.read(nc::stdscr()).unwrap_or_default()
+}
+
+/// Returns the configuration path given with `--config <path>`, if any. An error
+/// is returned if the path is missing.
+fn config_path() -> Result<Option<PathBuf>, config::Error> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let path = if arg == "--config" {
+            args.next().unwrap_or_default()
+        } else if arg.starts_with("--config=") {
+            arg["--config=".len()..].to_owned()
+        } else {
+            continue;
+        };
+        if path.is_empty() {
+            return Err(config::Error::Invalid("--config requires a path".to_owned()));
+        }
+        return Ok(Some(PathBuf::from(path)));
+    }
+    Ok(None)
+}
+
 pub struct Dobro {
+    config: Arc<Config>,
     pandora: Arc<Pandora>,
     player: Player,
+    saved: SavedState,
+    ui: Layout,
+
+    // Stations deleted in this session, the last one at the end.
+    deleted: Vec<DeletedStation>,
 }
 
 impl Dobro {
     /// Creates a new Dobro instance.
-    pub fn new(pandora: Pandora) -> Self {
+    pub fn new(pandora: Pandora, config: Config, saved: SavedState) -> Self {
+        let ui = Layout::new(&config);
+        let config = Arc::new(config);
         let pandora = Arc::new(pandora);
 
         Dobro {
-            player: Player::new(&pandora),
+            player: Player::new(&pandora, &config),
+            config: config,
             pandora: pandora,
+            saved: saved,
+            ui: ui,
+            deleted: Vec::new(),
         }
     }
 
+    /// Returns a reference to the user configuration.
+    pub fn config(&self) -> &Arc<Config> {
+        &self.config
+    }
+
     /// Returns a reference to the pandora handler.
     pub fn pandora(&self) -> &Arc<Pandora> {
         &self.pandora
@@ -104,4 +232,30 @@ impl Dobro {
     pub fn player_mut(&mut self) -> &mut Player {
         &mut self.player
     }
+
+    /// Returns a reference to the state saved between sessions.
+    pub fn saved(&self) -> &SavedState {
+        &self.saved
+    }
+
+    /// Returns a mutable reference to the state saved between sessions.
+    pub fn saved_mut(&mut self) -> &mut SavedState {
+        &mut self.saved
+    }
+
+    /// Returns a mutable reference to the stations deleted in this session,
+    /// the last one at the end.
+    pub fn deleted_mut(&mut self) -> &mut Vec<DeletedStation> {
+        &mut self.deleted
+    }
+
+    /// Returns a mutable reference to the screen layout.
+    pub fn ui(&mut self) -> &mut Layout {
+        &mut self.ui
+    }
+
+    /// Redraws the panes of the layout showing the player state.
+    pub fn draw(&mut self) {
+        self.ui.draw(&self.player.state());
+    }
 }
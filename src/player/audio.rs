@@ -1,57 +1,407 @@
 use super::error::Error;
+use super::sink::{AudioSink, Format};
 
-use ao;
 use earwax::{Earwax, Timestamp, LogLevel};
 
+use std::collections::VecDeque;
 use std::sync::{Once, ONCE_INIT};
+use std::time::{Duration, Instant};
 static START: Once = ONCE_INIT;
 
-/// Type for audio streaming audio that hides the details of earwax and ao-rs handling.
-pub struct Audio {
+/// Number of chunks decoded ahead of time when a stream is buffered.
+const BUFFER_CHUNKS: usize = 64;
+
+/// How far (in seconds) a seek can land from the requested time before the
+/// stream is re-opened and decoded up to that time instead.
+const SEEK_TOLERANCE: i64 = 2;
+
+/// How long (in seconds) a stream is decoded towards a seek it couldn't do
+/// directly. Decoding waits on the download, so playback goes on from wherever
+/// it got by then rather than holding the player.
+const SEEK_DECODE_LIMIT: u64 = 3;
+
+/// How early (in seconds) a stream can end before it counts as interrupted.
+const END_TOLERANCE: i64 = 2;
+
+/// Decoded stream that is not attached to an audio device yet. Unlike [Audio](struct.Audio.html)
+/// it can be sent between threads, so it can be opened and buffered in the background.
+pub struct Stream {
+    // Location opened again to rewind, seek or reconnect.
+    url: String,
     earwax: Earwax,
-    driver: ao::Driver,
-    format: ao::Format,
-    device: ao::Device,
+    buffer: VecDeque<(Vec<i8>, Timestamp)>,
+    gain: f32,
+    // True once a chunk was taken from the stream.
+    started: bool,
+    // True once a seek reached the end of the stream.
+    ended: bool,
 }
 
-impl Audio {
-    /// Tries to initialize a new stream for the given URL.
+impl Stream {
+    /// Tries to open a new stream for the given URL.
     pub fn new(url: &str) -> Result<Self, Error> {
+        Self::with_source(url, url)
+    }
+
+    /// Tries to open a new stream that is decoded from `source`, but opened again
+    /// from `url`. Used for sources that can only be read once, such as pipes.
+    pub fn with_source(source: &str, url: &str) -> Result<Self, Error> {
         // #[cfg(not(debug_assertions))]
         START.call_once(|| { Earwax::set_log_level(LogLevel::Error); });
 
-        let earwax = try!(Earwax::new(url));
-        let driver = try!(ao::Driver::new());
-        let format = ao::Format::new();
-        let device = try!(ao::Device::new(&driver, &format, None));
+        let earwax = try!(Earwax::new(source));
 
-        Ok(Audio {
+        Ok(Stream {
+               url: url.to_owned(),
                earwax: earwax,
-               driver: driver,
-               format: format,
-               device: device,
+               buffer: VecDeque::with_capacity(BUFFER_CHUNKS),
+               gain: 1.0,
+               started: false,
+               ended: false,
            })
     }
 
-    /// Plays the next chunk of the stream to the default audio device.
+    /// Returns the format of the decoded PCM data.
+    pub fn format(&self) -> Format {
+        Format {
+            rate: self.earwax.info().sample_rate as u32,
+            // Earwax always converts to stereo.
+            channels: 2,
+        }
+    }
+
+    /// Returns the next decoded chunk, from the buffer first.
+    fn next_chunk(&mut self) -> Option<(Vec<i8>, Timestamp)> {
+        if self.ended {
+            return None;
+        }
+        self.started = true;
+        match self.buffer.pop_front() {
+            Some(chunk) => Some(chunk),
+            None => self.earwax.spit().map(|chunk| (chunk.data.to_vec(), chunk.time)),
+        }
+    }
+
+    /// Sets the gain in dB applied to the whole stream.
+    pub fn set_gain(&mut self, db: f32) {
+        self.gain = 10f32.powf(db / 20.0);
+    }
+
+    /// Opens the stream again from its start if any chunk was taken from it,
+    /// and buffers it.
+    pub fn rewind(&mut self) -> Result<(), Error> {
+        if !self.started {
+            return Ok(());
+        }
+        self.earwax = try!(Earwax::new(&self.url));
+        self.buffer.clear();
+        self.started = false;
+        self.ended = false;
+        self.buffer();
+        Ok(())
+    }
+
+    /// Decodes the first chunks of the stream ahead of time, so playback can
+    /// start without waiting for the network.
+    pub fn buffer(&mut self) {
+        while self.buffer.len() < BUFFER_CHUNKS {
+            match self.earwax.spit() {
+                Some(chunk) => self.buffer.push_back((chunk.data.to_vec(), chunk.time)),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Type for audio streaming audio that hides the details of earwax handling. The
+/// decoded audio is played to the [AudioSink](../sink/trait.AudioSink.html) given
+/// on every call, so the same sink outlives the tracks.
+pub struct Audio {
+    stream: Stream,
+
+    // Software volume from 0.0 to 1.0, and the buffer for the scaled samples.
+    volume: f32,
+    samples: Vec<i8>,
+
+    // Extra attenuation from 0.0 to 1.0 applied when the gain would clip.
+    limiter: f32,
+
+    // Second of the last chunk played.
+    position: i64,
+
+    // Time at the end of the last chunk played, counted from the played samples
+    // since the chunk timestamps only have whole seconds.
+    clock: f64,
+
+    // Times the stream was resumed after being interrupted.
+    resumes: u32,
+}
+
+impl Audio {
+    /// Creates a new audio for the given stream.
+    pub fn new(stream: Stream) -> Self {
+        Audio {
+            stream: stream,
+
+            volume: 1.0,
+            samples: Vec::new(),
+
+            limiter: 1.0,
+
+            position: 0,
+            clock: 0.0,
+
+            resumes: 0,
+        }
+    }
+
+    /// Returns the format of the decoded PCM data.
+    pub fn format(&self) -> Format {
+        self.stream.format()
+    }
+
+    /// Replaces the stream played by this audio.
+    pub fn set_stream(&mut self, stream: Stream) {
+        self.stream = stream;
+        self.limiter = 1.0;
+        self.position = 0;
+        self.clock = 0.0;
+        self.resumes = 0;
+    }
+
+    /// Returns how many times the stream was resumed after being interrupted.
+    pub fn resumes(&self) -> u32 {
+        self.resumes
+    }
+
+    /// Sets the volume, from 0 (silence) to 100 (unchanged samples).
+    pub fn set_volume(&mut self, volume: u8) {
+        self.volume = volume.min(100) as f32 / 100.0;
+    }
+
+    /// Plays the next chunk of the stream to the given sink.
+    /// # Returns
+    /// If there was a chunk to play, the value returned is a tuple where the first
+    /// element is the current timestamp, and the second element is the total timestamp.
+    /// `None` at the end of the stream, and `Error::Interrupted` if the stream stopped
+    /// before its end (e.g. the connection dropped).
+    pub fn play(&mut self,
+                sink: &mut AudioSink)
+                -> Result<Option<(Timestamp, Timestamp)>, Error> {
+        let duration = self.stream.earwax.info().duration;
+        let gain = self.volume * self.stream.gain;
+        let time = match self.stream.next_chunk() {
+            Some((data, time)) => {
+                self.limiter = scale(&data, gain, self.limiter, &mut self.samples);
+                time
+            }
+            None => return self.end(),
+        };
+        try!(sink.play(&self.samples));
+        self.advance(time);
+        Ok(Some((time, duration)))
+    }
+
+    /// Plays the next chunk of the stream mixed with the next chunk of `next`. The
+    /// stream is scaled by `1 - fade` and `next` by `fade`, so a fade going from 0 to
+    /// 1 crossfades from one into the other. Nothing is taken from `next` until
+    /// the fade is over 0.
+    ///
     /// # Returns
-    /// If playback was successful (at getting next stream chunk), the value returned
-    /// is a tuple where the first element is the current timestamp, and the second
-    /// element is the total timestamp.
-    pub fn play(&mut self) -> Result<(Timestamp, Timestamp), ()> {
-        let duration = self.earwax.info().duration;
-        if let Some(chunk) = self.earwax.spit() {
-            self.device.play(chunk.data);
-            Ok((chunk.time, duration))
+    /// Same as [play](#method.play), for the current stream.
+    pub fn play_mixed(&mut self,
+                      sink: &mut AudioSink,
+                      next: &mut Stream,
+                      fade: f32)
+                      -> Result<Option<(Timestamp, Timestamp)>, Error> {
+        if fade <= 0.0 {
+            return self.play(sink);
+        }
+        let duration = self.stream.earwax.info().duration;
+        let fade = fade.min(1.0);
+        match self.stream.next_chunk() {
+            Some((data, time)) => {
+                let next_data = next.next_chunk().map(|(data, _)| data).unwrap_or_default();
+                mix(&data,
+                    self.volume * self.stream.gain * (1.0 - fade),
+                    &next_data,
+                    self.volume * next.gain * fade,
+                    &mut self.samples);
+                try!(sink.play(&self.samples));
+                self.advance(time);
+                Ok(Some((time, duration)))
+            }
+            None => self.end(),
+        }
+    }
+
+    /// Returns the seconds left in the stream, `None` if its length is unknown.
+    pub fn remaining(&self) -> Option<f64> {
+        let duration = self.stream.earwax.info().duration.seconds();
+        if duration > 0 {
+            Some(duration as f64 - self.clock)
         } else {
-            Err(())
+            None
         }
     }
 
-    /// Plays all the chunks remaining in the stream to the default audio the device.
-    pub fn play_all(&mut self) {
-        while let Some(chunk) = self.earwax.spit() {
-            self.device.play(chunk.data);
+    /// Updates the position after playing the samples of the chunk at the given
+    /// time. The clock is kept within the second of the chunk.
+    fn advance(&mut self, time: Timestamp) {
+        let format = self.stream.format();
+        let bytes_per_second = format.rate as f64 * format.channels as f64 * 2.0;
+        let length = self.samples.len() as f64 / bytes_per_second;
+        let start = time.seconds() as f64;
+        self.position = time.seconds();
+        self.clock = (self.clock + length).max(start).min(start + 1.0 + length);
+    }
+
+    /// Opens the stream again and continues from the last chunk played, after the
+    /// stream was interrupted.
+    ///
+    /// # Returns
+    /// The second playback continues from.
+    pub fn reconnect(&mut self) -> Result<i64, Error> {
+        let position = self.position;
+        self.stream.earwax = try!(Earwax::new(&self.stream.url));
+        let position = try!(self.seek(position));
+        self.resumes += 1;
+        Ok(position)
+    }
+
+    /// Returns the result of reaching the end of the stream: `None` if the whole
+    /// stream was played, `Error::Interrupted` if it stopped early.
+    fn end(&self) -> Result<Option<(Timestamp, Timestamp)>, Error> {
+        let duration = self.stream.earwax.info().duration.seconds();
+        if duration > 0 && self.position + END_TOLERANCE < duration {
+            Err(Error::Interrupted(self.position))
+        } else {
+            Ok(None)
         }
     }
+
+    /// Moves playback to the given second. A seek to the end (or past it) ends
+    /// the stream, so the next play finishes the track. If the stream can't seek
+    /// directly (or the seek lands somewhere else), the URL is opened again and
+    /// decoded towards the requested time for up to `SEEK_DECODE_LIMIT` seconds.
+    ///
+    /// # Returns
+    /// The second playback continues from.
+    pub fn seek(&mut self, seconds: i64) -> Result<i64, Error> {
+        let duration = self.stream.earwax.info().duration.seconds();
+        let seconds = seconds.max(0);
+        self.stream.buffer.clear();
+        self.stream.ended = false;
+        if duration > 0 && seconds >= duration {
+            return Ok(self.end_at(duration));
+        }
+
+        self.stream.earwax.seek(seconds);
+        let landed = match self.stream.earwax.spit() {
+            Some(chunk) => (chunk.data.to_vec(), chunk.time),
+            None => return Ok(self.end_at(duration.max(seconds))),
+        };
+        if (landed.1.seconds() - seconds).abs() <= SEEK_TOLERANCE {
+            self.stream.buffer.push_back(landed);
+            return Ok(self.land_at(seconds));
+        }
+
+        // Direct seeking didn't work, decode from the start instead.
+        let started = Instant::now();
+        let limit = Duration::from_secs(SEEK_DECODE_LIMIT);
+        let mut earwax = try!(Earwax::new(&self.stream.url));
+        let mut landed = None;
+        while let Some(chunk) = earwax.spit() {
+            let time = chunk.time.seconds();
+            if time >= seconds || started.elapsed() >= limit {
+                self.stream.buffer.push_back((chunk.data.to_vec(), chunk.time));
+                landed = Some(time.min(seconds));
+                break;
+            }
+        }
+        self.stream.earwax = earwax;
+        match landed {
+            Some(landed) => Ok(self.land_at(landed)),
+            None => Ok(self.end_at(duration.max(seconds))),
+        }
+    }
+
+    /// Sets the position after a seek that landed at the given second.
+    fn land_at(&mut self, seconds: i64) -> i64 {
+        self.position = seconds;
+        self.clock = seconds as f64;
+        seconds
+    }
+
+    /// Ends the stream after a seek to its end, at the given second.
+    fn end_at(&mut self, seconds: i64) -> i64 {
+        self.stream.ended = true;
+        self.land_at(seconds)
+    }
+
+    /// Plays all the chunks remaining in the stream to the given sink.
+    pub fn play_all(&mut self, sink: &mut AudioSink) -> Result<(), Error> {
+        while let Some(_) = try!(self.play(sink)) {}
+        Ok(())
+    }
+}
+
+/// How fast the limiter goes back to no attenuation, per chunk.
+const LIMITER_RELEASE: f32 = 1.02;
+
+/// Scales the given 16-bit little-endian PCM data by `gain`, and writes the result
+/// to `output`. Chunks that would clip are attenuated by a limiter, which recovers
+/// slowly over the next chunks.
+///
+/// # Returns
+/// The limiter attenuation for the next chunk.
+fn scale(data: &[i8], gain: f32, limiter: f32, output: &mut Vec<i8>) -> f32 {
+    output.clear();
+    if gain == 1.0 && limiter >= 1.0 {
+        output.extend_from_slice(data);
+        return limiter;
+    }
+
+    let sample = |pair: &[i8]| (pair[0] as u8 as u16 | (pair[1] as u8 as u16) << 8) as i16;
+    let peak = data.chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (sample(pair) as f32).abs())
+        .fold(0.0, f32::max);
+
+    let mut limiter = (limiter * LIMITER_RELEASE).min(1.0);
+    if peak * gain * limiter > i16::max_value() as f32 {
+        limiter = i16::max_value() as f32 / (peak * gain);
+    }
+
+    let gain = gain * limiter;
+    for pair in data.chunks(2) {
+        if pair.len() < 2 {
+            output.push(pair[0]);
+            continue;
+        }
+        let scaled = (sample(pair) as f32 * gain) as i16 as u16;
+        output.push(scaled as u8 as i8);
+        output.push((scaled >> 8) as u8 as i8);
+    }
+    limiter
+}
+
+/// Mixes two chunks of 16-bit little-endian PCM data scaled by their gains, and
+/// writes the result to `output`. The shorter chunk is padded with silence.
+fn mix(a: &[i8], a_gain: f32, b: &[i8], b_gain: f32, output: &mut Vec<i8>) {
+    let sample = |data: &[i8], i: usize| if i + 1 < data.len() {
+        (data[i] as u8 as u16 | (data[i + 1] as u8 as u16) << 8) as i16 as f32
+    } else {
+        0.0
+    };
+
+    output.clear();
+    let len = a.len().max(b.len());
+    for i in (0..len).filter(|i| i % 2 == 0) {
+        let mixed = sample(a, i) * a_gain + sample(b, i) * b_gain;
+        let mixed = mixed.max(i16::min_value() as f32).min(i16::max_value() as f32);
+        let mixed = mixed as i16 as u16;
+        output.push(mixed as u8 as i8);
+        output.push((mixed >> 8) as u8 as i8);
+    }
 }
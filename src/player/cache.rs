@@ -0,0 +1,234 @@
+//! On-disk cache of the played tracks. Tracks are written to the cache while they
+//! are downloaded for playback, so a track that comes up again is played from
+//! disk. When the cache grows over its size limit, the least recently used tracks
+//! are removed.
+
+use super::error::Error;
+
+use hyper::Client;
+use hyper::header::ContentLength;
+use libc;
+
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Name of the file listing the cached tracks, least recently used first.
+const INDEX: &'static str = "index";
+
+/// Cache of tracks in a directory, limited to a size in bytes. Tracks are
+/// looked up by a key, such as the song they play.
+pub struct Cache {
+    dir: PathBuf,
+    max_size: u64,
+    // Guards the index, which is updated from the loader threads.
+    lock: Mutex<()>,
+}
+
+impl Cache {
+    /// Creates a cache in the given directory.
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Cache {
+            dir: dir,
+            max_size: max_size,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns the path of the cached track with the given key, if any, and
+    /// marks it as the most recently used.
+    pub fn get(&self, key: &str) -> Option<PathBuf> {
+        let path = self.path(key);
+        if !path.is_file() {
+            return None;
+        }
+        let _lock = self.lock.lock().unwrap();
+        let _ = self.touch(key);
+        Some(path)
+    }
+
+    /// Starts downloading the track with the given key from the given URL, and
+    /// returns a pipe the download can be decoded from while it goes on. The
+    /// download is added to the cache once all of it was read from the pipe, and
+    /// thrown away if the pipe is closed before that or the connection drops.
+    pub fn tee(cache: &Arc<Cache>, key: &str, url: &str) -> Result<Tee, Error> {
+        try!(fs::create_dir_all(&cache.dir));
+
+        let mut response = try!(Client::new().get(url).send());
+        if !response.status.is_success() {
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      format!("download failed with {}", response.status))
+                .into());
+        }
+        let length = response.headers.get::<ContentLength>().map(|length| length.0);
+
+        let path = cache.path(key);
+        let fifo = path.with_extension("fifo");
+        let _ = fs::remove_file(&fifo);
+        let name = try!(CString::new(fifo.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)));
+        if unsafe { libc::mkfifo(name.as_ptr(), 0o600) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let cache = cache.clone();
+        let key = key.to_owned();
+        let writer = fifo.clone();
+        thread::spawn(move || {
+            // Downloads to a temporary file first, so a partial download never
+            // looks like a cached track.
+            let partial = path.with_extension("part");
+            match copy_both(&mut response, &writer, &partial) {
+                Ok(read) if length.map_or(true, |length| length == read) => {
+                    let _lock = cache.lock.lock().unwrap();
+                    if fs::rename(&partial, &path).is_ok() {
+                        let _ = cache.touch(&key);
+                        let _ = cache.evict(&key);
+                    }
+                }
+                _ => {
+                    let _ = fs::remove_file(&partial);
+                }
+            }
+        });
+
+        Ok(Tee { fifo: fifo })
+    }
+
+    /// Returns the path for the track with the given key.
+    fn path(&self, key: &str) -> PathBuf {
+        let name: String = key.chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect();
+        self.dir.join(name)
+    }
+
+    /// Moves the given key to the end of the index.
+    fn touch(&self, key: &str) -> io::Result<()> {
+        let mut keys = self.index();
+        keys.retain(|k| k != key);
+        keys.push(key.to_owned());
+        self.write_index(&keys)
+    }
+
+    /// Removes the least recently used tracks until the cache fits its size limit.
+    /// The given key (the track just stored) is never removed.
+    fn evict(&self, keep: &str) -> io::Result<()> {
+        let mut keys: Vec<String> = self.index()
+            .into_iter()
+            .filter(|key| self.path(key).is_file())
+            .collect();
+        let mut size: u64 = keys.iter().map(|key| file_size(&self.path(key))).sum();
+
+        while size > self.max_size && keys.len() > 0 && keys[0] != keep {
+            let key = keys.remove(0);
+            let path = self.path(&key);
+            size -= file_size(&path);
+            try!(fs::remove_file(path));
+        }
+        self.write_index(&keys)
+    }
+
+    /// Reads the index, least recently used first.
+    fn index(&self) -> Vec<String> {
+        match File::open(self.dir.join(INDEX)) {
+            Ok(file) => {
+                BufReader::new(file)
+                    .lines()
+                    .filter_map(|line| line.ok())
+                    .filter(|line| !line.is_empty())
+                    .collect()
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn write_index(&self, keys: &[String]) -> io::Result<()> {
+        let mut file = try!(File::create(self.dir.join(INDEX)));
+        for key in keys {
+            try!(writeln!(file, "{}", key));
+        }
+        Ok(())
+    }
+}
+
+/// Pipe of a download started by [tee](struct.Cache.html#method.tee).
+pub struct Tee {
+    fifo: PathBuf,
+}
+
+impl Tee {
+    /// Returns the path the download is read from.
+    pub fn path(&self) -> &Path {
+        &self.fifo
+    }
+}
+
+impl Drop for Tee {
+    fn drop(&mut self) {
+        // The download thread waits for a reader before it starts writing, so
+        // one is opened (and closed right away) in case the decoder never did.
+        let _ = OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(&self.fifo);
+        let _ = fs::remove_file(&self.fifo);
+    }
+}
+
+/// Copies the whole response to both the given pipe and file.
+///
+/// # Returns
+/// The number of bytes copied.
+fn copy_both(response: &mut Read, pipe: &Path, path: &Path) -> io::Result<u64> {
+    let mut pipe = try!(OpenOptions::new().write(true).open(pipe));
+    let mut file = try!(File::create(path));
+    let mut buffer = [0; 8192];
+    let mut copied = 0;
+    loop {
+        let read = match response.read(&mut buffer) {
+            Ok(0) => return Ok(copied),
+            Ok(read) => read,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        try!(pipe.write_all(&buffer[..read]));
+        try!(file.write_all(&buffer[..read]));
+        copied += read as u64;
+    }
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cache;
+    use super::super::playlist::parse_track;
+
+    use serde_json::builder::ObjectBuilder;
+
+    use std::path::PathBuf;
+
+    #[test]
+    fn same_song_shares_a_path() {
+        let cache = Cache::new(PathBuf::from("cache"), 0);
+        let paths: Vec<PathBuf> = ["first-token", "second-token"]
+            .iter()
+            .map(|token| {
+                let item = ObjectBuilder::new()
+                    .insert("trackToken", *token)
+                    .insert("songIdentifier", "12345")
+                    .insert("songName", "Song")
+                    .build();
+                let track = parse_track(item).unwrap();
+                assert_eq!(track.track.track_token.as_ref().map(|t| t.as_str()), Some(*token));
+                cache.path(&track.song_id.unwrap())
+            })
+            .collect();
+        assert_eq!(paths[0], paths[1]);
+    }
+}
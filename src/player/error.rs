@@ -1,38 +1,64 @@
 use std::error::Error as StdError;
+use std::io::Error as IoError;
 
 use ao::error::Error as AoError;
 use earwax::error::Error as EarwaxError;
+use hyper::error::Error as HttpError;
 use pandora::error::Error as PandoraError;
 
 /// Composite error type for the player.
 #[derive(Debug)]
 pub enum Error {
     Ao(AoError),
+    Io(IoError),
     Earwax(EarwaxError),
+    Http(HttpError),
     Pandora(PandoraError),
+    /// The stream stopped before its end, at the given second.
+    Interrupted(i64),
+    /// The named track was skipped because its stream couldn't be opened.
+    Skipped(String, Box<Error>),
+    /// None of the tracks of the station could be played.
+    NoTracks,
 }
 
 impl StdError for Error {
     fn description(&self) -> &str {
         match *self {
             Error::Ao(ref e) => e.description(),
+            Error::Io(ref e) => e.description(),
             Error::Earwax(ref e) => e.description(),
+            Error::Http(ref e) => e.description(),
             Error::Pandora(ref e) => e.description(),
+            Error::Interrupted(_) => "stream interrupted",
+            Error::Skipped(..) => "track skipped",
+            Error::NoTracks => "no playable tracks",
         }
     }
 
     fn cause(&self) -> Option<&StdError> {
         match *self {
             Error::Ao(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
             Error::Earwax(ref e) => Some(e),
+            Error::Http(ref e) => Some(e),
             Error::Pandora(ref e) => Some(e),
+            Error::Interrupted(_) | Error::NoTracks => None,
+            Error::Skipped(_, ref e) => Some(&**e),
         }
     }
 }
 
 impl ::std::fmt::Display for Error {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-        write!(f, "{:?}", self)
+        match *self {
+            Error::Interrupted(at) => {
+                write!(f, "Stream interrupted at {:02}:{:02}", at / 60, at % 60)
+            }
+            Error::Skipped(ref track, ref e) => write!(f, "Skipped \"{}\": {}", track, e),
+            Error::NoTracks => write!(f, "No more tracks of the station could be played"),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
 
@@ -42,12 +68,24 @@ impl From<AoError> for Error {
     }
 }
 
+impl From<IoError> for Error {
+    fn from(error: IoError) -> Error {
+        Error::Io(error)
+    }
+}
+
 impl From<EarwaxError> for Error {
     fn from(error: EarwaxError) -> Error {
         Error::Earwax(error)
     }
 }
 
+impl From<HttpError> for Error {
+    fn from(error: HttpError) -> Error {
+        Error::Http(error)
+    }
+}
+
 impl From<PandoraError> for Error {
     fn from(error: PandoraError) -> Error {
         Error::Pandora(error)
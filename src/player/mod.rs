@@ -1,5 +1,8 @@
 mod audio;
+mod cache;
 mod error;
+mod playlist;
+mod sink;
 mod state;
 mod thread;
 mod track_loader;
@@ -8,6 +11,8 @@ use self::error::Error;
 pub use self::state::{PlayerState, PlayerStatus};
 use self::thread::spawn_player;
 
+use config::{AudioQuality, Config};
+
 use ao;
 use pandora::{Pandora, Station};
 
@@ -49,15 +54,17 @@ impl Drop for Player {
 
 impl Player {
     /// Creates a new Player.
-    pub fn new(pandora: &Arc<Pandora>) -> Self {
+    pub fn new(pandora: &Arc<Pandora>, config: &Arc<Config>) -> Self {
         // Initialize AO before anything else.
         let ao = ao::Ao::new();
 
-        let state = Arc::new(Mutex::new(PlayerState::new()));
+        let mut state = PlayerState::new();
+        state.set_quality(config.audio_quality());
+        let state = Arc::new(Mutex::new(state));
 
         let (external_sender, receiver) = channel();
         let (sender, external_receiver) = channel();
-        let player_handle = spawn_player(pandora, &state, sender, receiver);
+        let player_handle = spawn_player(pandora, config, &state, sender, receiver);
 
         Player {
             ao: ao,
@@ -107,6 +114,22 @@ impl Player {
         self.sender.send(PlayerAction::Skip).unwrap();
     }
 
+    /// Moves playback of the current track to the given second.
+    pub fn seek(&mut self, seconds: i64) {
+        self.sender.send(PlayerAction::Seek(seconds)).unwrap();
+    }
+
+    /// Moves playback of the current track by the given seconds, backwards when
+    /// negative.
+    pub fn seek_by(&mut self, seconds: i64) {
+        self.sender.send(PlayerAction::SeekBy(seconds)).unwrap();
+    }
+
+    /// Plays the current track again from the start.
+    pub fn replay(&mut self) {
+        self.seek(0);
+    }
+
     /// Toggles pause / unpause.
     pub fn toggle_pause(&mut self) {
         if self.is_paused() {
@@ -116,6 +139,26 @@ impl Player {
         }
     }
 
+    /// Sets the audio quality used for the tracks loaded from now on.
+    pub fn set_quality(&mut self, quality: AudioQuality) {
+        self.sender.send(PlayerAction::SetQuality(quality)).unwrap();
+    }
+
+    /// Sets the volume, from 0 to 100.
+    pub fn set_volume(&mut self, volume: u8) {
+        self.sender.send(PlayerAction::SetVolume(volume)).unwrap();
+    }
+
+    /// Returns the current volume, from 0 to 100.
+    pub fn volume(&self) -> u8 {
+        self.state.lock().unwrap().volume()
+    }
+
+    /// Toggles mute / unmute.
+    pub fn toggle_mute(&mut self) {
+        self.sender.send(PlayerAction::ToggleMute).unwrap();
+    }
+
     /// Requests the player to send an event reporting its current status.
     pub fn report(&self) {
         self.sender.send(PlayerAction::Report).unwrap();
@@ -155,6 +198,11 @@ impl Player {
         self.state.lock().unwrap().status().is_paused()
     }
 
+    /// Returns true if the player is muted.
+    pub fn is_muted(&self) -> bool {
+        self.state.lock().unwrap().is_muted()
+    }
+
     /// Returns true if the player is shutdown.
     pub fn is_shutdown(&self) -> bool {
         self.state.lock().unwrap().status().is_shutdown()
@@ -195,6 +243,13 @@ pub enum PlayerAction {
     Pause,
     Unpause,
     Skip,
+    Seek(i64),
+    SeekBy(i64),
+
+    // Audio related actions.
+    SetQuality(AudioQuality),
+    SetVolume(u8),
+    ToggleMute,
 
     // Misc actions.
     Report,
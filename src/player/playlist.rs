@@ -0,0 +1,58 @@
+//! Station playlists along with the replay gain and song of each track. Pandora
+//! sends them with every track, but the pandora crate doesn't deserialize them.
+
+use pandora::{Pandora, Station, Track};
+use pandora::error::Result;
+use pandora::method::Method;
+use pandora::stations::ToStationToken;
+
+use serde_json::{self, Value};
+use serde_json::builder::ObjectBuilder;
+
+/// Track from a playlist.
+#[derive(Debug, Clone)]
+pub struct PlaylistTrack {
+    pub track: Track,
+    /// Replay gain in dB, if Pandora sent one.
+    pub gain: Option<f32>,
+    /// Identifier of the song. Unlike the track token, which is new in every
+    /// playlist, it's the same whenever the song comes up.
+    pub song_id: Option<String>,
+}
+
+/// Gets the current tracklist of the given station from Pandora.
+pub fn list(pandora: &Pandora, station: &Station) -> Result<Vec<PlaylistTrack>> {
+    let body = ObjectBuilder::new()
+        .insert("stationToken", station.to_station_token())
+        .build();
+    let playlist = try!(pandora.post::<Value>(Method::StationGetPlaylist, Some(body)));
+
+    let items = playlist.find("items").and_then(Value::as_array).cloned().unwrap_or_default();
+    let mut tracklist = Vec::with_capacity(items.len());
+    for item in items {
+        tracklist.push(try!(parse_track(item)));
+    }
+    Ok(tracklist)
+}
+
+/// Parses a track of a playlist as sent by Pandora.
+pub fn parse_track(item: Value) -> Result<PlaylistTrack> {
+    let gain = item.find("trackGain").and_then(gain);
+    let song_id = item.find("songIdentifier").and_then(Value::as_str).map(str::to_owned);
+    Ok(PlaylistTrack {
+           track: try!(serde_json::from_value::<Track>(item)),
+           gain: gain,
+           song_id: song_id,
+       })
+}
+
+/// Parses the track gain, which Pandora sends as a string (e.g. "-2.35").
+fn gain(value: &Value) -> Option<f32> {
+    match *value {
+        Value::String(ref gain) => gain.trim().parse().ok(),
+        Value::F64(gain) => Some(gain as f32),
+        Value::I64(gain) => Some(gain as f32),
+        Value::U64(gain) => Some(gain as f32),
+        _ => None,
+    }
+}
@@ -0,0 +1,228 @@
+//! Outputs for the decoded audio. Every sink receives 16-bit little-endian
+//! interleaved PCM, in the [Format](struct.Format.html) it was opened with.
+
+use super::error::Error;
+
+use config::Output;
+
+use ao;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Stdout, Write};
+use std::path::Path;
+
+/// Bits per sample.
+const BITS: u16 = 16;
+
+/// Sample rate and channels of the PCM data given to a sink.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Format {
+    pub rate: u32,
+    pub channels: u32,
+}
+
+/// Destination of the decoded audio. A sink is opened once and reused for every
+/// track.
+pub trait AudioSink {
+    /// Plays (or writes) the given PCM data.
+    fn play(&mut self, samples: &[i8]) -> Result<(), Error>;
+
+    /// Changes the format of the PCM data given from now on. Sinks that can't
+    /// change format keep the one they were opened with.
+    fn set_format(&mut self, _: Format) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Opens the sink selected in the configuration for the given format.
+pub fn open(output: &Output, format: Format) -> Result<Box<AudioSink>, Error> {
+    Ok(match *output {
+           Output::Ao { ref driver, ref options } => {
+               Box::new(try!(AoSink::new(driver.as_ref().map(|d| d.as_str()), options, format)))
+           }
+           Output::Wav(ref path) => Box::new(try!(FileSink::wav(path, format))),
+           Output::Raw(ref path) => Box::new(try!(FileSink::raw(path, format))),
+           Output::Null => Box::new(NullSink),
+           Output::Stdout => Box::new(StdoutSink::new()),
+       })
+}
+
+/// Plays the audio through libao. The device is only reopened when the format
+/// changes.
+pub struct AoSink {
+    driver: ao::Driver,
+    settings: ao::Settings,
+    format: Format,
+    device: Option<ao::Device>,
+}
+
+impl AoSink {
+    /// Opens a device for the given format with the given driver (the default one
+    /// if `None`) and driver options.
+    pub fn new(driver: Option<&str>,
+               options: &BTreeMap<String, String>,
+               format: Format)
+               -> Result<Self, Error> {
+        let driver = match driver {
+            Some(name) => try!(ao::Driver::with_name(name)),
+            None => try!(ao::Driver::new()),
+        };
+
+        let mut settings = ao::Settings::new();
+        for (key, value) in options {
+            settings.append(key, value);
+        }
+
+        let mut sink = AoSink {
+            driver: driver,
+            settings: settings,
+            format: format,
+            device: None,
+        };
+        try!(sink.open());
+        Ok(sink)
+    }
+
+    /// Opens the device for the current format, closing the previous one first
+    /// since some drivers only allow one at a time.
+    fn open(&mut self) -> Result<(), Error> {
+        let mut ao_format = ao::Format::new();
+        ao_format.bits = BITS as u32;
+        ao_format.rate = self.format.rate;
+        ao_format.channels = self.format.channels;
+        ao_format.byte_format = ao::ByteFormat::Little;
+
+        self.device = None;
+        self.device = Some(try!(ao::Device::new(&self.driver, &ao_format, Some(&self.settings))));
+        Ok(())
+    }
+}
+
+impl AudioSink for AoSink {
+    fn play(&mut self, samples: &[i8]) -> Result<(), Error> {
+        if let Some(ref device) = self.device {
+            device.play(samples);
+        }
+        Ok(())
+    }
+
+    fn set_format(&mut self, format: Format) -> Result<(), Error> {
+        if format != self.format || self.device.is_none() {
+            self.format = format;
+            try!(self.open());
+        }
+        Ok(())
+    }
+}
+
+/// Writes the audio to a file, either as raw PCM or as a WAV file. The whole file
+/// keeps the format it was opened with.
+pub struct FileSink {
+    file: BufWriter<File>,
+    format: Format,
+    wav: bool,
+    data_len: u32,
+}
+
+impl FileSink {
+    /// Creates (or truncates) a WAV file at the given path. The sizes in the
+    /// header are filled in when the sink is dropped.
+    pub fn wav(path: &Path, format: Format) -> Result<Self, Error> {
+        let mut sink = FileSink {
+            file: BufWriter::new(try!(File::create(path))),
+            format: format,
+            wav: true,
+            data_len: 0,
+        };
+        try!(sink.write_header());
+        Ok(sink)
+    }
+
+    /// Creates (or truncates) a raw PCM file at the given path.
+    pub fn raw(path: &Path, format: Format) -> Result<Self, Error> {
+        Ok(FileSink {
+               file: BufWriter::new(try!(File::create(path))),
+               format: format,
+               wav: false,
+               data_len: 0,
+           })
+    }
+
+    /// Writes the WAV header for the current data length.
+    fn write_header(&mut self) -> io::Result<()> {
+        let channels = self.format.channels as u16;
+        let rate = self.format.rate;
+        let block_align = channels * BITS / 8;
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&le32(36 + self.data_len));
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&le32(16));
+        header.extend_from_slice(&le16(1)); // PCM.
+        header.extend_from_slice(&le16(channels));
+        header.extend_from_slice(&le32(rate));
+        header.extend_from_slice(&le32(rate * block_align as u32));
+        header.extend_from_slice(&le16(block_align));
+        header.extend_from_slice(&le16(BITS));
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&le32(self.data_len));
+        self.file.write_all(&header)
+    }
+}
+
+impl AudioSink for FileSink {
+    fn play(&mut self, samples: &[i8]) -> Result<(), Error> {
+        let bytes: Vec<u8> = samples.iter().map(|&s| s as u8).collect();
+        try!(self.file.write_all(&bytes));
+        self.data_len = self.data_len.saturating_add(bytes.len() as u32);
+        Ok(())
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        if self.wav {
+            if self.file.seek(SeekFrom::Start(0)).is_ok() {
+                let _ = self.write_header();
+            }
+        }
+        let _ = self.file.flush();
+    }
+}
+
+/// Discards the audio.
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn play(&mut self, _: &[i8]) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Writes raw PCM to the standard output, so it can be piped to other tools.
+pub struct StdoutSink {
+    stdout: Stdout,
+}
+
+impl StdoutSink {
+    pub fn new() -> Self {
+        StdoutSink { stdout: io::stdout() }
+    }
+}
+
+impl AudioSink for StdoutSink {
+    fn play(&mut self, samples: &[i8]) -> Result<(), Error> {
+        let bytes: Vec<u8> = samples.iter().map(|&s| s as u8).collect();
+        try!(self.stdout.write_all(&bytes));
+        Ok(())
+    }
+}
+
+fn le16(value: u16) -> [u8; 2] {
+    [value as u8, (value >> 8) as u8]
+}
+
+fn le32(value: u32) -> [u8; 4] {
+    [value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]
+}
@@ -1,3 +1,4 @@
+use config::AudioQuality;
 use pandora::{Station, Track};
 
 /// Player state. It holds the information for the station, track, progress,
@@ -7,6 +8,9 @@ pub struct PlayerState {
     station: Option<Station>,
     track: Option<Track>,
     progress: Option<(i64, i64)>,
+    quality: AudioQuality,
+    volume: u8,
+    muted: bool,
     status: PlayerStatus,
 }
 
@@ -17,6 +21,9 @@ impl PlayerState {
             station: None,
             track: None,
             progress: None,
+            quality: AudioQuality::High,
+            volume: 100,
+            muted: false,
             status: PlayerStatus::Shutdown,
         }
     }
@@ -63,6 +70,36 @@ impl PlayerState {
         self.progress = None;
     }
 
+    pub fn quality(&self) -> AudioQuality {
+        self.quality
+    }
+
+    pub fn set_quality(&mut self, quality: AudioQuality) {
+        self.quality = quality;
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, volume: u8) {
+        self.volume = volume.min(100);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Returns the volume that should be applied to the audio, taking mute
+    /// into account.
+    pub fn effective_volume(&self) -> u8 {
+        if self.muted { 0 } else { self.volume }
+    }
+
     pub fn status(&self) -> PlayerStatus {
         self.status.clone()
     }
@@ -1,19 +1,36 @@
-use super::audio::Audio;
+use super::audio::{Audio, Stream};
+use super::cache::Cache;
 use super::error::Error;
+use super::playlist;
+use super::sink::{self, AudioSink, Format};
 use super::track_loader::TrackLoader;
 use super::PlayerAction;
 use super::state::{PlayerState, PlayerStatus};
 
-use ao;
+use config::Config;
+
 use pandora::{Pandora, Station, Track};
 
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use std::sync::{Arc, Mutex, Condvar};
 use std::sync::mpsc::{channel, Sender, Receiver};
 
+/// Number of attempts at resuming an interrupted stream.
+const RECONNECT_ATTEMPTS: u32 = 3;
+/// Milliseconds to wait before the second attempt, doubled after every failed one.
+const RECONNECT_BACKOFF: u64 = 1000;
+/// Number of times a track is resumed before it's given up, so a stream that
+/// keeps dropping doesn't stall the station.
+const MAX_RESUMES: u32 = 3;
+/// Milliseconds between the checks for a pause or an action while waiting to
+/// reconnect.
+const RECONNECT_POLL: u64 = 100;
+
 /// This function starts the event and player thread.
 pub fn spawn_player(pandora: &Arc<Pandora>,
+                    config: &Arc<Config>,
                     main_state: &Arc<Mutex<PlayerState>>,
                     main_sender: Sender<Result<PlayerStatus, Error>>,
                     main_receiver: Receiver<PlayerAction>)
@@ -51,6 +68,15 @@ pub fn spawn_player(pandora: &Arc<Pandora>,
                                cvar.notify_one();
                            }
 
+                           PlayerAction::SetVolume(volume) => {
+                               state.lock().unwrap().set_volume(volume);
+                           }
+                           PlayerAction::ToggleMute => {
+                               let mut state = state.lock().unwrap();
+                               let muted = state.is_muted();
+                               state.set_muted(!muted);
+                           }
+
                            PlayerAction::Report => {
                                sender
                                    .send(Ok(state.lock().unwrap().status().clone()))
@@ -62,6 +88,16 @@ pub fn spawn_player(pandora: &Arc<Pandora>,
                                break;
                            }
 
+                           // Seeks are done while paused, so the player thread
+                           // is woken up for them.
+                           PlayerAction::Seek(_) |
+                           PlayerAction::SeekBy(_) => {
+                               event_sender.send(action).unwrap();
+                               let &(ref lock, ref cvar) = &*pause_pair;
+                               let _paused = lock.lock().unwrap();
+                               cvar.notify_one();
+                           }
+
                            action => {
                                event_sender.send(action).unwrap();
                            }
@@ -74,6 +110,8 @@ pub fn spawn_player(pandora: &Arc<Pandora>,
     // and takes care of fetching the tracks. All the events this thread receives are
     // the events forwarded from the 'event' thread.
     let pandora = pandora.clone();
+    let config = config.clone();
+    let cache = config.cache_dir().map(|dir| Arc::new(Cache::new(dir, config.cache_size())));
     let state = main_state.clone();
     let pause_pair = main_pause_pair.clone();
     let sender = main_sender.clone();
@@ -84,10 +122,14 @@ pub fn spawn_player(pandora: &Arc<Pandora>,
             // Context of our player.
             let mut ctx = ThreadContext {
                 pandora: pandora,
+                config: config,
                 state: state,
                 pause_pair: pause_pair,
                 sender: sender,
                 receiver: event_receiver,
+                sink: None,
+                cache: cache,
+                pending: None,
             };
 
             // Finite state machine loop.
@@ -109,10 +151,17 @@ pub fn spawn_player(pandora: &Arc<Pandora>,
 /// Context struct for our finite state machine.
 struct ThreadContext {
     pub pandora: Arc<Pandora>,
+    pub config: Arc<Config>,
     pub state: Arc<Mutex<PlayerState>>,
     pub pause_pair: Arc<(Mutex<bool>, Condvar)>,
     pub sender: Sender<Result<PlayerStatus, Error>>,
     pub receiver: Receiver<PlayerAction>,
+    // Opened with the first track and kept until the thread exits, it only
+    // changes format when a track needs it.
+    pub sink: Option<Box<AudioSink>>,
+    pub cache: Option<Arc<Cache>>,
+    // Action received while waiting to reconnect, handled before the others.
+    pub pending: Option<PlayerAction>,
 }
 
 impl ThreadContext {
@@ -127,16 +176,59 @@ impl ThreadContext {
         self.sender.send(Err(error)).unwrap();
     }
 
+    /// Reports the error that stopped the given station, and returns the Standby
+    /// state to wait for the user.
+    pub fn stop_with_error(&mut self, station: &Station, error: Error) -> ThreadFSM {
+        self.state.lock().unwrap().clear_info();
+        self.send_error(error);
+        self.send_status(PlayerStatus::Stopped(station.clone()));
+        self.send_status(PlayerStatus::Standby);
+        ThreadFSM::new()
+    }
+
+    /// Opens the sink for the given format, or changes the format of the one
+    /// already open.
+    pub fn prepare_sink(&mut self, format: Format) -> Result<(), Error> {
+        if let Some(ref mut sink) = self.sink {
+            return sink.set_format(format);
+        }
+        self.sink = Some(try!(sink::open(self.config.output(), format)));
+        Ok(())
+    }
+
     /// Blocks the current thread and returns the next available
     /// action.
     pub fn action(&mut self) -> Option<PlayerAction> {
-        self.receiver.recv().ok()
+        self.pending.take().or_else(|| self.receiver.recv().ok())
     }
 
     /// Checks for a pending action without blocking the current
     /// thread.
     pub fn try_action(&mut self) -> Option<PlayerAction> {
-        self.receiver.try_recv().ok()
+        self.pending.take().or_else(|| self.receiver.try_recv().ok())
+    }
+
+    /// Waits up to the given time for the player to be paused or for an action,
+    /// which is kept for the next [try_action](#method.try_action).
+    ///
+    /// # Returns
+    /// True if the wait was cut short.
+    pub fn wait_action(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if *self.pause_pair.0.lock().unwrap() {
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            let step = (deadline - now).min(Duration::from_millis(RECONNECT_POLL));
+            if let Ok(action) = self.receiver.recv_timeout(step) {
+                self.pending = Some(action);
+                return true;
+            }
+        }
     }
 }
 
@@ -158,6 +250,8 @@ enum ThreadFSM {
         track_loader: TrackLoader,
         track: Track,
         audio: Audio,
+        // Next track and stream while crossfading into it.
+        fading: Option<(Track, Stream)>,
     },
 }
 
@@ -204,17 +298,24 @@ impl ThreadFSM {
                 track_loader,
                 track,
                 audio,
-            } => Self::update_playing(ctx, station, track_loader, track, audio),
+                fading,
+            } => Self::update_playing(ctx, station, track_loader, track, audio, fading),
 
             _ => self,
         }
     }
 
     fn update_standby(ctx: &mut ThreadContext) -> ThreadFSM {
-        if let Some(PlayerAction::Play(station)) = ctx.action() {
-            ctx.state.lock().unwrap().set_station(station.clone());
-            ctx.send_status(PlayerStatus::Started(station.clone()));
-            return Self::new_station(station);
+        match ctx.action() {
+            Some(PlayerAction::Play(station)) => {
+                ctx.state.lock().unwrap().set_station(station.clone());
+                ctx.send_status(PlayerStatus::Started(station.clone()));
+                return Self::new_station(station);
+            }
+            Some(PlayerAction::SetQuality(quality)) => {
+                ctx.state.lock().unwrap().set_quality(quality);
+            }
+            _ => (),
         }
 
         // Stay in Standby state.
@@ -223,13 +324,25 @@ impl ThreadFSM {
 
     fn update_station(ctx: &mut ThreadContext, station: Station) -> ThreadFSM {
         ctx.send_status(PlayerStatus::Fetching(station.clone()));
-        match ctx.pandora.stations().playlist(&station).list() {
+        match playlist::list(&ctx.pandora, &station) {
+            // Nothing to play, fetching again would only loop.
+            Ok(ref tracklist) if tracklist.is_empty() => {
+                ctx.stop_with_error(&station, Error::NoTracks)
+            }
             Ok(tracklist) => {
-                Self::new_track(station, TrackLoader::new(tracklist.into_iter().collect()))
+                let quality = ctx.state.lock().unwrap().quality();
+                let track_loader = TrackLoader::new(&ctx.pandora,
+                                                    &ctx.config,
+                                                    ctx.cache.clone(),
+                                                    station.clone(),
+                                                    tracklist.into_iter().collect(),
+                                                    quality);
+                Self::new_track(station, track_loader)
             }
             Err(e) => {
-                ctx.send_error(e.into());
-                Self::new_station(station)
+                // Pandora already tried to log in again, so report the error once
+                // and wait for the user instead of retrying forever.
+                ctx.stop_with_error(&station, e.into())
             }
         }
     }
@@ -238,33 +351,70 @@ impl ThreadFSM {
                     station: Station,
                     mut track_loader: TrackLoader)
                     -> ThreadFSM {
-        if let Some((track, audio)) = track_loader.next() {
+        let next = track_loader.next();
+        let skipped = Self::report_skipped(ctx, &mut track_loader);
+        if let Some((track, audio)) = next {
+            if let Err(e) = ctx.prepare_sink(audio.format()) {
+                return ctx.stop_with_error(&station, e);
+            }
+
             ctx.state.lock().unwrap().set_track(track.clone());
             ctx.send_status(PlayerStatus::Playing(track.clone()));
-            return Self::new_playing(station, track_loader, track, audio);
+            return Self::new_playing(station, track_loader, track, audio, None);
+        }
+
+        if let Some(e) = track_loader.take_refill_error() {
+            return ctx.stop_with_error(&station, e);
+        }
+        if !skipped {
+            // The tracklist ran out before it was refilled, fetch a new playlist
+            // like when the station started.
+            return Self::new_station(station);
         }
-        Self::new_station(station)
+        // The skipped tracks were reported, wait for the user.
+        ctx.stop_with_error(&station, Error::NoTracks)
     }
 
     fn update_playing(ctx: &mut ThreadContext,
                       station: Station,
-                      track_loader: TrackLoader,
+                      mut track_loader: TrackLoader,
                       track: Track,
-                      mut audio: Audio)
+                      mut audio: Audio,
+                      mut fading: Option<(Track, Stream)>)
                       -> ThreadFSM {
-        // Pauses.
+        // Pauses. Seeks are done while paused, the first other action waits
+        // for the unpause.
+        let mut action = None;
         {
             let &(ref lock, ref cvar) = &*ctx.pause_pair.clone();
             let mut paused = lock.lock().unwrap();
-            while *paused {
+            if *paused {
                 ctx.send_status(PlayerStatus::Paused(track.clone()));
-                paused = cvar.wait(paused).unwrap();
+                while *paused {
+                    while action.is_none() {
+                        match ctx.try_action() {
+                            Some(PlayerAction::Seek(seconds)) => {
+                                Self::seek(ctx, &mut audio, &mut fading, seconds);
+                            }
+                            Some(PlayerAction::SeekBy(seconds)) => {
+                                Self::seek_by(ctx, &mut audio, &mut fading, seconds);
+                            }
+                            other => {
+                                action = other;
+                                break;
+                            }
+                        }
+                    }
+                    paused = cvar.wait(paused).unwrap();
+                }
                 ctx.send_status(PlayerStatus::Playing(track.clone()));
             }
         }
 
+        Self::report_skipped(ctx, &mut track_loader);
+
         // Actions.
-        if let Some(action) = ctx.try_action() {
+        if let Some(action) = action.or_else(|| ctx.try_action()) {
             match action {
                 PlayerAction::Play(new_station) => {
                     ctx.state.lock().unwrap().clear_info();
@@ -286,9 +436,30 @@ impl ThreadFSM {
                     ctx.state.lock().unwrap().clear_track();
                     ctx.state.lock().unwrap().clear_progress();
                     ctx.send_status(PlayerStatus::Finished(track.clone()));
+                    // The track we were fading into is next.
+                    if let Some((next_track, stream)) = fading {
+                        return Self::fade_into(ctx,
+                                               station,
+                                               track_loader,
+                                               next_track,
+                                               audio,
+                                               stream);
+                    }
                     return Self::new_track(station, track_loader);
                 }
 
+                PlayerAction::Seek(seconds) => {
+                    Self::seek(ctx, &mut audio, &mut fading, seconds);
+                }
+                PlayerAction::SeekBy(seconds) => {
+                    Self::seek_by(ctx, &mut audio, &mut fading, seconds);
+                }
+
+                PlayerAction::SetQuality(quality) => {
+                    ctx.state.lock().unwrap().set_quality(quality);
+                    track_loader.set_quality(quality);
+                }
+
                 PlayerAction::Exit => {
                     ctx.state.lock().unwrap().clear_info();
                     ctx.send_status(PlayerStatus::Finished(track.clone()));
@@ -301,20 +472,172 @@ impl ThreadFSM {
             }
         }
 
+        // Crossfade into the next track once we are close to the end, if the next
+        // track is ready by then.
+        let crossfade = ctx.config.crossfade() as f64;
+        let remaining = audio.remaining();
+        if let Some(remaining) = remaining {
+            if fading.is_none() && crossfade > 0.0 && remaining <= crossfade {
+                fading = track_loader.try_next_stream();
+            }
+        }
+
         // Playback.
-        if let Ok((current, duration)) = audio.play() {
-            ctx.state
-                .lock()
-                .unwrap()
-                .set_progress(current.seconds(), duration.seconds());
-        } else {
-            ctx.state.lock().unwrap().clear_track();
-            ctx.state.lock().unwrap().clear_progress();
-            ctx.send_status(PlayerStatus::Finished(track.clone()));
-            return Self::new_track(station, track_loader);
+        let volume = ctx.state.lock().unwrap().effective_volume();
+        audio.set_volume(volume);
+        let format = audio.format();
+        let played = match (ctx.sink.as_mut(), fading.as_mut()) {
+            // Streams can only be mixed if they have the same format.
+            (Some(sink), Some(&mut (_, ref mut stream))) if stream.format() == format => {
+                let fade = remaining.map_or(0.0, |remaining| 1.0 - remaining / crossfade);
+                audio.play_mixed(&mut **sink, stream, fade as f32)
+            }
+            (Some(sink), _) => audio.play(&mut **sink),
+            (None, _) => Ok(None),
+        };
+        match played {
+            Ok(Some((current, duration))) => {
+                ctx.state
+                    .lock()
+                    .unwrap()
+                    .set_progress(current.seconds(), duration.seconds());
+            }
+            Ok(None) => {
+                return Self::finish_track(ctx, station, track_loader, track, audio, fading);
+            }
+            Err(Error::Interrupted(at)) => {
+                ctx.send_error(Error::Interrupted(at));
+                if audio.resumes() >= MAX_RESUMES {
+                    return Self::finish_track(ctx, station, track_loader, track, audio, fading);
+                }
+                if let Err(e) = Self::reconnect(ctx, &mut audio) {
+                    ctx.send_error(e);
+                    return Self::finish_track(ctx, station, track_loader, track, audio, fading);
+                }
+            }
+            Err(e) => {
+                // The output failed, there's no point in going on with the station.
+                ctx.send_status(PlayerStatus::Finished(track.clone()));
+                return ctx.stop_with_error(&station, e);
+            }
+        }
+
+        return Self::new_playing(station, track_loader, track, audio, fading);
+    }
+
+    /// Reports the current track as finished, and continues with the next one.
+    fn finish_track(ctx: &mut ThreadContext,
+                    station: Station,
+                    track_loader: TrackLoader,
+                    track: Track,
+                    audio: Audio,
+                    fading: Option<(Track, Stream)>)
+                    -> ThreadFSM {
+        ctx.state.lock().unwrap().clear_track();
+        ctx.state.lock().unwrap().clear_progress();
+        ctx.send_status(PlayerStatus::Finished(track.clone()));
+        if let Some((next_track, stream)) = fading {
+            return Self::fade_into(ctx, station, track_loader, next_track, audio, stream);
+        }
+        Self::new_track(station, track_loader)
+    }
+
+    /// Tries to resume an interrupted stream, waiting longer after every failed
+    /// attempt. A pause or an action cuts the wait short and leaves the stream
+    /// interrupted, so they are handled first and the stream resumed after.
+    fn reconnect(ctx: &mut ThreadContext, audio: &mut Audio) -> Result<(), Error> {
+        let mut backoff = RECONNECT_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match audio.reconnect() {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    if attempt >= RECONNECT_ATTEMPTS {
+                        return Err(e);
+                    }
+                }
+            }
+            if ctx.wait_action(Duration::from_millis(backoff)) {
+                return Ok(());
+            }
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    /// Continues playing the track we were crossfading into.
+    fn fade_into(ctx: &mut ThreadContext,
+                 station: Station,
+                 track_loader: TrackLoader,
+                 track: Track,
+                 mut audio: Audio,
+                 stream: Stream)
+                 -> ThreadFSM {
+        if let Err(e) = ctx.prepare_sink(stream.format()) {
+            return ctx.stop_with_error(&station, e);
+        }
+        audio.set_stream(stream);
+        ctx.state.lock().unwrap().set_track(track.clone());
+        ctx.send_status(PlayerStatus::Playing(track.clone()));
+        Self::new_playing(station, track_loader, track, audio, None)
+    }
+
+    /// Sends the errors of the tracks the loader skipped, and returns true if
+    /// there were any.
+    fn report_skipped(ctx: &mut ThreadContext, track_loader: &mut TrackLoader) -> bool {
+        let errors = track_loader.take_errors();
+        let skipped = !errors.is_empty();
+        for error in errors {
+            ctx.send_error(error);
+        }
+        skipped
+    }
+
+    /// Seeks the given audio and updates the progress in the player state. If
+    /// the seek lands before the crossfade, the track we were fading into starts
+    /// over when the crossfade is reached again.
+    fn seek(ctx: &mut ThreadContext,
+            audio: &mut Audio,
+            fading: &mut Option<(Track, Stream)>,
+            seconds: i64) {
+        match audio.seek(seconds) {
+            Ok(seconds) => {
+                let mut state = ctx.state.lock().unwrap();
+                if let Some((_, total)) = state.progress() {
+                    state.set_progress(seconds, total);
+                }
+            }
+            Err(e) => {
+                ctx.send_error(e);
+                return;
+            }
+        }
+
+        let crossfade = ctx.config.crossfade() as f64;
+        if audio.remaining().map_or(true, |remaining| remaining > crossfade) {
+            let rewound = match *fading {
+                Some((_, ref mut stream)) => stream.rewind(),
+                None => Ok(()),
+            };
+            if let Err(e) = rewound {
+                ctx.send_error(e);
+                *fading = None;
+            }
         }
+    }
 
-        return Self::new_playing(station, track_loader, track, audio);
+    /// Seeks the given audio by the given seconds from the current position.
+    fn seek_by(ctx: &mut ThreadContext,
+               audio: &mut Audio,
+               fading: &mut Option<(Track, Stream)>,
+               seconds: i64) {
+        let current = ctx.state
+            .lock()
+            .unwrap()
+            .progress()
+            .map(|(current, _)| current)
+            .unwrap_or(0);
+        Self::seek(ctx, audio, fading, current + seconds);
     }
 
     // ----------------
@@ -339,13 +662,15 @@ impl ThreadFSM {
     fn new_playing(station: Station,
                    track_loader: TrackLoader,
                    track: Track,
-                   audio: Audio)
+                   audio: Audio,
+                   fading: Option<(Track, Stream)>)
                    -> ThreadFSM {
         ThreadFSM::Playing {
             station: station,
             track_loader: track_loader,
             track: track,
             audio: audio,
+            fading: fading,
         }
     }
 }
@@ -1,87 +1,389 @@
-use pandora::Track;
-use super::audio::Audio;
+use config::{AudioQuality, Config};
+use pandora::{Pandora, Station, Track};
+use super::audio::{Audio, Stream};
+use super::cache::Cache;
+use super::error::Error;
+use super::playlist::{self, PlaylistTrack};
 
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex, Condvar};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
+use std::time::Duration;
 
-/// TrackLoader type for loading tracks in the background.
+/// Number of attempts at opening a stream before giving up on it.
+const OPEN_ATTEMPTS: u32 = 3;
+/// Milliseconds to wait before the second attempt, doubled after every failed one.
+const OPEN_BACKOFF: u64 = 500;
+
+/// TrackLoader type for loading tracks in the background. While a track plays,
+/// the stream for the next one is opened and buffered in a separate thread, and
+/// the tracklist is refilled from the station playlist before it runs out.
 pub struct TrackLoader {
-    tracklist: Arc<Mutex<VecDeque<Track>>>,
-    next: Arc<Mutex<Option<(Track, Audio)>>>,
+    pandora: Arc<Pandora>,
+    station: Station,
+    // Read by the fetch thread for every track it opens.
+    quality: Arc<Mutex<AudioQuality>>,
+    refill_threshold: usize,
+    replay_gain: Option<f32>,
+    cache: Option<Arc<Cache>>,
+
+    tracklist: Arc<Mutex<VecDeque<PlaylistTrack>>>,
+    next: Arc<Mutex<Option<(PlaylistTrack, Stream)>>>,
     fetching: Arc<(Mutex<bool>, Condvar)>,
+    refilling: Arc<(Mutex<bool>, Condvar)>,
+    cancelled: Arc<AtomicBool>,
+    // Tracks skipped because they couldn't be opened, until they are reported.
+    errors: Arc<Mutex<Vec<Error>>>,
+    // Error of the last refill, if the playlist couldn't be fetched.
+    refill_error: Arc<Mutex<Option<Error>>>,
+}
+
+impl Drop for TrackLoader {
+    fn drop(&mut self) {
+        // Any fetch still running is discarded when it finishes.
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
 }
 
 impl TrackLoader {
-    /// Creates a new TrackLoader form the given tracklist.
-    pub fn new(tracklist: VecDeque<Track>) -> Self {
+    /// Creates a new TrackLoader form the given station tracklist, and starts loading
+    /// the first track.
+    pub fn new(pandora: &Arc<Pandora>,
+               config: &Config,
+               cache: Option<Arc<Cache>>,
+               station: Station,
+               tracklist: VecDeque<PlaylistTrack>,
+               quality: AudioQuality)
+               -> Self {
         let mut track_loader = TrackLoader {
+            pandora: pandora.clone(),
+            station: station,
+            quality: Arc::new(Mutex::new(quality)),
+            refill_threshold: config.playlist_refill(),
+            replay_gain: config.replay_gain(),
+            cache: cache,
+
             tracklist: Arc::new(Mutex::new(tracklist)),
             next: Arc::new(Mutex::new(None)),
             fetching: Arc::new((Mutex::new(false), Condvar::new())),
+            refilling: Arc::new((Mutex::new(false), Condvar::new())),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            errors: Arc::new(Mutex::new(Vec::new())),
+            refill_error: Arc::new(Mutex::new(None)),
         };
-        // track_loader.fetch();
+        track_loader.fetch();
         track_loader
     }
 
     /// Returns the next track and audio, `None` is no more
     /// items available.
     pub fn next(&mut self) -> Option<(Track, Audio)> {
-        // // Wait until we are done fetching.
-        // {
-        //     let &(ref lock, ref cvar) = &*self.fetching;
-        //     let mut fetching = lock.lock().unwrap();
-        //     while *fetching {
-        //         fetching = cvar.wait(fetching).unwrap();
-        //     }
-        // }
-
-        // let next = self.next.lock().unwrap().take();
-        // self.fetch();
-        let next = pop_tracklist(self.tracklist.clone());
-        return next;
-    }
-
-    // /// Fetches the next track in the background.
-    // fn fetch(&mut self) {
-    //     let tracklist = self.tracklist.clone();
-    //     let next = self.next.clone();
-    //     let pair = self.fetching.clone();
-
-    //     if tracklist.lock().unwrap().len() > 0 {
-    //         let &(ref lock, ref cvar) = &*self.fetching;
-    //         let mut fetching = lock.lock().unwrap();
-    //         *fetching = true;
-
-    //         thread::spawn(move || {
-    //             if next.lock().unwrap().is_none() {
-    //                 *next.lock().unwrap() = pop_tracklist(tracklist);
-    //             }
-    //             let &(ref lock, ref cvar) = &*pair;
-    //             let mut fetching = lock.lock().unwrap();
-    //             *fetching = false;
-    //             cvar.notify_one();
-    //         });
-    //     }
-    // }
-}
+        self.next_stream().map(|(track, stream)| (track, Audio::new(stream)))
+    }
 
-/// Pops the next track from the tracklist and returns it along
-/// with the audio.
-fn pop_tracklist(tracklist: Arc<Mutex<VecDeque<Track>>>) -> Option<(Track, Audio)> {
-    if let Some((track, audio)) =
-        tracklist
-            .lock()
-            .unwrap()
-            .pop_front()
-            .and_then(|track| track.track_audio.clone().map(|audio| (track, audio))) {
-        let audio = match Audio::new(&audio.high_quality.audio_url) {
-            Ok(audio) => audio,
-            Err(e) => {
+    /// Returns the next track and its stream if it was already loaded, without
+    /// waiting for it.
+    pub fn try_next_stream(&mut self) -> Option<(Track, Stream)> {
+        {
+            let &(ref lock, _) = &*self.fetching;
+            if *lock.lock().unwrap() {
                 return None;
             }
+        }
+        let next = self.next.lock().unwrap().take();
+        if next.is_some() {
+            self.refill();
+            self.fetch();
+        }
+        next.map(|(next, stream)| (next.track, stream))
+    }
+
+    /// Returns the next track and its stream, waiting for it to load. `None` if no
+    /// more items are available, or if none of the tracks of a refill could be
+    /// opened.
+    pub fn next_stream(&mut self) -> Option<(Track, Stream)> {
+        let mut refilled = false;
+        loop {
+            // Wait until we are done fetching.
+            {
+                let &(ref lock, ref cvar) = &*self.fetching;
+                let mut fetching = lock.lock().unwrap();
+                while *fetching {
+                    fetching = cvar.wait(fetching).unwrap();
+                }
+            }
+
+            let next = self.next.lock().unwrap().take();
+            self.refill();
+            self.fetch();
+
+            match next {
+                Some((next, stream)) => return Some((next.track, stream)),
+                None => {
+                    // Nothing left to play, but the tracklist might be in the
+                    // middle of a refill. The station is given up if the tracks
+                    // of the refill couldn't be played either.
+                    if refilled || !self.wait_refill() {
+                        return None;
+                    }
+                    refilled = true;
+                    self.fetch();
+                }
+            }
+        }
+    }
+
+    /// Returns the errors of the tracks skipped since the last call.
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        self.errors.lock().unwrap().drain(..).collect()
+    }
+
+    /// Returns the error of the last refill, if the playlist couldn't be fetched.
+    pub fn take_refill_error(&mut self) -> Option<Error> {
+        self.refill_error.lock().unwrap().take()
+    }
+
+    /// Sets the quality for the tracks fetched from now on. The next track is
+    /// opened again if it was already loaded at the previous quality.
+    pub fn set_quality(&mut self, quality: AudioQuality) {
+        {
+            let mut current = self.quality.lock().unwrap();
+            if *current == quality {
+                return;
+            }
+            *current = quality;
+        }
+        let next = self.next.lock().unwrap().take();
+        if let Some((track, _)) = next {
+            self.tracklist.lock().unwrap().push_front(track);
+            self.fetch();
+        }
+    }
+
+    /// Fetches the next playlist in the background if the tracklist is running
+    /// low, and appends it to the tracklist.
+    fn refill(&mut self) {
+        if self.tracklist.lock().unwrap().len() >= self.refill_threshold {
+            return;
+        }
+
+        let &(ref lock, _) = &*self.refilling;
+        let mut refilling = lock.lock().unwrap();
+        if *refilling {
+            return;
+        }
+        *refilling = true;
+
+        let pandora = self.pandora.clone();
+        let station = self.station.clone();
+        let tracklist = self.tracklist.clone();
+        let pair = self.refilling.clone();
+        let cancelled = self.cancelled.clone();
+        let refill_error = self.refill_error.clone();
+
+        thread::Builder::new()
+            .name("playlist_loader".to_string())
+            .spawn(move || {
+                let res = playlist::list(&pandora, &station);
+                if !cancelled.load(Ordering::SeqCst) {
+                    let error = match res {
+                        Ok(playlist) => {
+                            tracklist.lock().unwrap().extend(playlist);
+                            None
+                        }
+                        Err(e) => Some(e.into()),
+                    };
+                    *refill_error.lock().unwrap() = error;
+                }
+                let &(ref lock, ref cvar) = &*pair;
+                let mut refilling = lock.lock().unwrap();
+                *refilling = false;
+                cvar.notify_one();
+            })
+            .unwrap();
+    }
+
+    /// Waits for a running refill (if any) to finish.
+    ///
+    /// # Returns
+    /// True if there are tracks in the tracklist afterwards.
+    fn wait_refill(&mut self) -> bool {
+        {
+            let &(ref lock, ref cvar) = &*self.refilling;
+            let mut refilling = lock.lock().unwrap();
+            while *refilling {
+                refilling = cvar.wait(refilling).unwrap();
+            }
+        }
+        self.tracklist.lock().unwrap().len() > 0
+    }
+
+    /// Fetches the next track in the background, unless a fetch is already running.
+    fn fetch(&mut self) {
+        let quality = self.quality.clone();
+        let replay_gain = self.replay_gain;
+        let cache = self.cache.clone();
+        let tracklist = self.tracklist.clone();
+        let next = self.next.clone();
+        let pair = self.fetching.clone();
+        let cancelled = self.cancelled.clone();
+        let errors = self.errors.clone();
+
+        let &(ref lock, _) = &*self.fetching;
+        let mut fetching = lock.lock().unwrap();
+        if !*fetching && tracklist.lock().unwrap().len() > 0 {
+            *fetching = true;
+
+            thread::Builder::new()
+                .name("track_loader".to_string())
+                .spawn(move || {
+                    while !cancelled.load(Ordering::SeqCst) && next.lock().unwrap().is_none() {
+                        let requested = *quality.lock().unwrap();
+                        let loaded = pop_tracklist(&tracklist,
+                                                   requested,
+                                                   replay_gain,
+                                                   &cache,
+                                                   &cancelled,
+                                                   &errors);
+                        match loaded {
+                            Some(Some((track, stream))) => {
+                                if cancelled.load(Ordering::SeqCst) {
+                                    break;
+                                }
+                                if *quality.lock().unwrap() == requested {
+                                    *next.lock().unwrap() = Some((track, stream));
+                                } else {
+                                    // The quality changed while the track was
+                                    // opened, open it again.
+                                    tracklist.lock().unwrap().push_front(track);
+                                }
+                            }
+                            // The track couldn't be opened (and was reported), try the
+                            // next one.
+                            Some(None) => (),
+                            None => break,
+                        }
+                    }
+                    let &(ref lock, ref cvar) = &*pair;
+                    let mut fetching = lock.lock().unwrap();
+                    *fetching = false;
+                    cvar.notify_one();
+                })
+                .unwrap();
+        }
+    }
+}
+
+/// Pops the next track from the tracklist and returns it along with its buffered
+/// stream. If the stream for the given quality is missing or can't be opened, the
+/// other qualities are tried. The stream gain is set from the track replay gain plus
+/// the given preamp, unless `replay_gain` is `None`.
+///
+/// With a cache, a cached copy of the track is played from disk, otherwise the track
+/// is written to the cache while it's streamed.
+///
+/// Every stream is retried with backoff before moving on. When the track can't be
+/// opened at all, the error is added to `errors` and the track is skipped.
+///
+/// # Returns
+/// * `None` when the tracklist is empty.
+/// * `Some(None)` when the popped track has no audio or none of its streams could
+/// be opened.
+fn pop_tracklist(tracklist: &Arc<Mutex<VecDeque<PlaylistTrack>>>,
+                 quality: AudioQuality,
+                 replay_gain: Option<f32>,
+                 cache: &Option<Arc<Cache>>,
+                 cancelled: &AtomicBool,
+                 errors: &Mutex<Vec<Error>>)
+                 -> Option<Option<(PlaylistTrack, Stream)>> {
+    let next = match tracklist.lock().unwrap().pop_front() {
+        Some(next) => next,
+        None => return None,
+    };
+    let gain = next.gain;
+
+    let audio = match next.track.track_audio.clone() {
+        Some(audio) => audio,
+        None => return Some(None),
+    };
+
+    // Cached by song, since the track token is new every time the song comes up.
+    let cache = match (cache.as_ref(), next.song_id.clone()) {
+        (Some(cache), Some(song_id)) => Some((cache, song_id)),
+        _ => None,
+    };
+    if let Some((cache, ref song_id)) = cache {
+        if let Some(path) = cache.get(song_id) {
+            if let Ok(stream) = Stream::new(&path.to_string_lossy()) {
+                return Some(Some((next, prepare(stream, gain, replay_gain))));
+            }
+        }
+    }
+
+    let mut error = None;
+    for quality in quality.fallbacks().iter() {
+        let url = match *quality {
+            AudioQuality::Low => &audio.low_quality.audio_url,
+            AudioQuality::Medium => &audio.medium_quality.audio_url,
+            AudioQuality::High => &audio.high_quality.audio_url,
         };
-        return Some((track, audio));
+        if url.is_empty() {
+            continue;
+        }
+        let tee = cache.as_ref().and_then(|&(cache, ref song_id)| {
+            retry(cancelled, || Cache::tee(cache, song_id, url)).ok()
+        });
+        let stream = tee.and_then(|tee| {
+            Stream::with_source(&tee.path().to_string_lossy(), url).ok()
+        });
+        // Streams from the network alone if the track can't be cached.
+        let stream = match stream {
+            Some(stream) => Ok(stream),
+            None => retry(cancelled, || Stream::new(url)),
+        };
+        match stream {
+            Ok(stream) => return Some(Some((next, prepare(stream, gain, replay_gain)))),
+            Err(e) => error = Some(e),
+        }
+    }
+
+    if let Some(error) = error {
+        if !cancelled.load(Ordering::SeqCst) {
+            let name = next.track.song_name.clone().unwrap_or("unknown track".to_owned());
+            errors.lock().unwrap().push(Error::Skipped(name, Box::new(error)));
+        }
+    }
+    Some(None)
+}
+
+/// Runs `f` until it succeeds, up to `OPEN_ATTEMPTS` times with exponential backoff
+/// between attempts. Gives up early if the loader is cancelled.
+fn retry<T, F>(cancelled: &AtomicBool, mut f: F) -> Result<T, Error>
+    where F: FnMut() -> Result<T, Error>
+{
+    let mut backoff = OPEN_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                if attempt >= OPEN_ATTEMPTS || cancelled.load(Ordering::SeqCst) {
+                    return Err(e);
+                }
+            }
+        }
+        thread::sleep(Duration::from_millis(backoff));
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+/// Sets the gain of a newly opened stream and buffers it.
+fn prepare(mut stream: Stream, gain: Option<f32>, replay_gain: Option<f32>) -> Stream {
+    if let Some(preamp) = replay_gain {
+        stream.set_gain(gain.unwrap_or(0.0) + preamp);
     }
-    None
+    stream.buffer();
+    stream
 }
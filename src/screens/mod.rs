@@ -2,7 +2,9 @@ mod station;
 mod station_add_variety;
 mod station_create;
 mod station_delete;
+mod station_manage;
 mod station_rename;
+mod station_restore;
 mod station_select;
 mod track_rate;
 
@@ -10,6 +12,8 @@ pub use self::station::StationScreen;
 pub use self::station_add_variety::StationAddVarietyScreen;
 pub use self::station_create::StationCreateScreen;
 pub use self::station_delete::StationDeleteScreen;
+pub use self::station_manage::StationManageScreen;
 pub use self::station_rename::StationRenameScreen;
+pub use self::station_restore::StationRestoreScreen;
 pub use self::station_select::StationSelectScreen;
 pub use self::track_rate::TrackRateScreen;
@@ -5,162 +5,374 @@ use super::super::Dobro;
 use super::StationAddVarietyScreen;
 use super::StationCreateScreen;
 use super::StationDeleteScreen;
+use super::StationManageScreen;
 use super::StationRenameScreen;
+use super::StationRestoreScreen;
 use super::StationSelectScreen;
 use super::TrackRateScreen;
 
+use config::{Binding, Key};
 use player::PlayerStatus;
-use ui::*;
 use state::*;
 
+use pandora::Station;
 use pandora::playlist::Track;
 
-use ncurses as nc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-static HELP_TEXT: &'static str = "Keybindings:
- '?' for help;
- 'n' to skip;
- 'p' to pause;
- 'c' to create station;
- 'r' to rename station;
- 'a' to add variety to station;
- 's' to change station;
- 'd' to delete station;
- '+' or '-' to rate the current track;
- 'q' to quit.";
+/// Seconds moved for each seek back / forward command.
+const SEEK_STEP: i64 = 10;
 
-pub struct StationScreen {}
+/// Updates without a key after which a partly typed key sequence is dropped.
+const SEQUENCE_TIMEOUT: u32 = 10;
+
+/// Volume change for each volume up / down command.
+const VOLUME_STEP: u8 = 5;
+
+/// Description of every command shown in the help text.
+static HELP_COMMANDS: &'static [(&'static str, &'static str)] = &[
+    ("help", "for help"),
+    ("skip", "to skip"),
+    ("pause", "to pause"),
+    ("replay", "to play the song again from the start"),
+    ("seek_back", "to go back 10 seconds"),
+    ("seek_forward", "to go forward 10 seconds"),
+    ("create", "to create station"),
+    ("rename", "to rename station"),
+    ("add_variety", "to add variety to station"),
+    ("select", "to change station"),
+    ("delete", "to delete station"),
+    ("undo", "to create the last deleted station again"),
+    ("manage", "to rename, delete or add variety to any station"),
+    ("quality", "to change the audio quality"),
+    ("volume_down", "to lower the volume"),
+    ("volume_up", "to raise the volume"),
+    ("mute", "to mute or unmute"),
+    ("love", "to love the current track"),
+    ("ban", "to ban the current track"),
+    ("command_line", "to type a command"),
+    ("quit", "to quit"),
+];
+
+/// Commands of the command line besides the ones in the keymap, with their
+/// arguments and description.
+static LINE_COMMANDS: &'static [(&'static str, &'static str, &'static str)] = &[
+    ("station", "<name>", "to play the station with the given name"),
+    ("create", "<search>", "to create a station from the search results"),
+    ("variety", "<search>", "to add variety from the search results"),
+    ("rename", "<name>", "to rename the station"),
+    ("rate", "+|-", "to love or ban the current track"),
+    ("volume", "<0-100>", "to set the volume"),
+];
+
+pub struct StationScreen {
+    // Set to false once the default station (if any) was tried.
+    autoplay: bool,
+
+    // Keys of the sequence being typed, and the updates since the last one.
+    pending: Vec<Key>,
+    idle: u32,
+}
 
 impl StationScreen {
     pub fn new() -> Self {
-        StationScreen {}
+        StationScreen {
+            autoplay: true,
+            pending: Vec::new(),
+            idle: 0,
+        }
     }
 
-    fn print_song(status: &str, track: &Track) {
+    /// Returns the help text for the active keymap and the command line.
+    fn help_text(ctx: &Dobro) -> String {
+        let mut help = "Keybindings:".to_owned();
+        for &(command, description) in HELP_COMMANDS {
+            let keys = ctx.config().keymap().keys(command);
+            if keys.is_empty() {
+                help.push_str(&format!("\n :{} {};", command, description));
+            } else {
+                help.push_str(&format!("\n '{}' or :{} {};",
+                                       keys.join("', '"),
+                                       command,
+                                       description));
+            }
+        }
+        help.pop();
+        help.push_str(".\nCommands:");
+        for &(command, arguments, description) in LINE_COMMANDS {
+            help.push_str(&format!("\n :{} {} {};", command, arguments, description));
+        }
+        help.pop();
+        help.push('.');
+        help
+    }
+
+    /// Finds the default station from the user configuration in the user
+    /// station list.
+    fn default_station(ctx: &Dobro) -> Option<Station> {
+        let name = match ctx.config().default_station() {
+            Some(name) => name.to_lowercase(),
+            None => return None,
+        };
+        ctx.pandora()
+            .stations()
+            .list()
+            .ok()
+            .and_then(|stations| {
+                          stations
+                              .into_iter()
+                              .find(|station| station.station_name.to_lowercase() == name)
+                      })
+    }
+
+    /// Finds the station with the given name in the user station list, or the
+    /// first one whose name starts with it.
+    fn find_station(ctx: &Dobro, name: &str) -> Option<Station> {
+        let name = name.to_lowercase();
+        let stations = match ctx.pandora().stations().list() {
+            Ok(stations) => stations,
+            Err(_) => return None,
+        };
+        let names: Vec<String> = stations.iter()
+            .map(|station| station.station_name.to_lowercase())
+            .collect();
+        names.iter()
+            .position(|station_name| *station_name == name)
+            .or_else(|| names.iter().position(|station_name| station_name.starts_with(&name)))
+            .map(|index| stations[index].clone())
+    }
+
+    fn print_song(ctx: &mut Dobro, status: &str, track: &Track) {
+        let unknown = "Unknown".to_owned();
         let loved = track.song_rating.unwrap_or(0) > 0;
-        nc::printw(&format!("{} \"{}\" by {}",
-                           status,
-                           track.song_name.as_ref().unwrap_or(&"Unknown".to_owned()),
-                           track.artist_name.as_ref().unwrap_or(&"Unknown".to_owned())));
-        nc::printw(&format!("{}\n", if loved { "  <3" } else { "" }));
+        ctx.ui().print(&format!("{} \"{}\" by {}{}\n",
+                                status,
+                                track.song_name.as_ref().unwrap_or(&unknown),
+                                track.artist_name.as_ref().unwrap_or(&unknown),
+                                if loved { "  <3" } else { "" }));
     }
 
-    fn print_progress(ctx: &mut Dobro) {
-        if let Some((current, total)) = ctx.player().state().progress() {
-            let total_mins = total / 60;
-            let total_secs = total % 60;
-            let mins = current / 60;
-            let secs = current % 60;
-
-            // Print seconds.
-            let mut y = 0;
-            let mut x = 0;
-            nc::getyx(nc::stdscr(), &mut y, &mut x);
-            nc::mv(y, 0);
-            nc::clrtoeol();
-            nc::printw(&format!("{:02}:{:02}/{:02}:{:02}",
-                               mins,
-                               secs,
-                               total_mins,
-                               total_secs));
-        }
-        nc::printw("\n");
+    /// Sets the player volume and prints the new value.
+    fn set_volume(ctx: &mut Dobro, volume: u8) {
+        let volume = volume.min(100);
+        ctx.player_mut().set_volume(volume);
+        ctx.ui().print(&format!("Volume set to {}%\n", volume));
     }
-}
 
-impl State for StationScreen {
-    fn resume(&mut self, ctx: &mut Dobro) {
-        let status = ctx.player().state().status().clone();
-
-        match status {
-            PlayerStatus::Playing(_) |
-            PlayerStatus::Paused(_) => {
-                nc::printw("\n\n");
-                ctx.player().report();
+    /// Runs the given command from the keymap.
+    fn run(&mut self, ctx: &mut Dobro, command: &str) -> Trans {
+        match command {
+            "help" => {
+                let help = Self::help_text(ctx);
+                ctx.ui().print(&format!("{}\n", help));
+            }
+            "skip" => ctx.player_mut().skip(),
+            "pause" => ctx.player_mut().toggle_pause(),
+            "replay" => ctx.player_mut().replay(),
+            "seek_back" => ctx.player_mut().seek_by(-SEEK_STEP),
+            "seek_forward" => ctx.player_mut().seek_by(SEEK_STEP),
+            "create" => return Trans::Push(Box::new(StationCreateScreen::new())),
+            "rename" => return Trans::Push(Box::new(StationRenameScreen::new())),
+            "add_variety" => return Trans::Push(Box::new(StationAddVarietyScreen::new())),
+            "select" => return Trans::Push(Box::new(StationSelectScreen::new())),
+            "delete" => return Trans::Push(Box::new(StationDeleteScreen::new())),
+            "undo" => return Trans::Push(Box::new(StationRestoreScreen::new())),
+            "manage" => return Trans::Push(Box::new(StationManageScreen::new())),
+            "quality" => {
+                let quality = ctx.player().state().quality().next();
+                ctx.player_mut().set_quality(quality);
+                ctx.ui().print(&format!("Audio quality set to {} (from the next track)\n",
+                                        quality.name()));
+            }
+            "volume_down" => {
+                let volume = ctx.player().volume().saturating_sub(VOLUME_STEP);
+                Self::set_volume(ctx, volume);
+            }
+            "volume_up" => {
+                let volume = ctx.player().volume().saturating_add(VOLUME_STEP);
+                Self::set_volume(ctx, volume);
+            }
+            "mute" => {
+                let muted = !ctx.player().is_muted();
+                ctx.player_mut().toggle_mute();
+                ctx.ui().print(if muted { "Muted\n" } else { "Unmuted\n" });
             }
+            "love" => return Trans::Push(Box::new(TrackRateScreen::new(true))),
+            "ban" => return Trans::Push(Box::new(TrackRateScreen::new(false))),
+            "command_line" => return self.command_line(ctx),
+            "quit" => return Trans::Quit,
             _ => (),
         };
+
+        Trans::None
     }
 
+    /// Reads a command on the status line and runs it.
+    fn command_line(&mut self, ctx: &mut Dobro) -> Trans {
+        let pandora = ctx.pandora().clone();
+        // Station names are only fetched when completing them.
+        let mut station_names: Option<Vec<String>> = None;
+        let line = ctx.ui().prompt_command(":", |line| {
+            if line.starts_with("station ") {
+                let names = station_names.get_or_insert_with(|| {
+                    pandora.stations().list()
+                        .map(|stations| stations.into_iter().map(|s| s.station_name).collect())
+                        .unwrap_or_default()
+                });
+                complete(&line["station ".len()..], names.iter().map(|name| name.as_str()))
+                    .into_iter()
+                    .map(|name| format!("station {}", name))
+                    .collect()
+            } else if !line.contains(' ') {
+                let commands = LINE_COMMANDS.iter()
+                    .map(|&(command, _, _)| command)
+                    .chain(HELP_COMMANDS.iter().map(|&(command, _)| command));
+                complete(line, commands)
+            } else {
+                Vec::new()
+            }
+        });
+
+        let line = match line {
+            Some(line) => line.trim().to_owned(),
+            None => return Trans::None,
+        };
+        if line.is_empty() {
+            return Trans::None;
+        }
+
+        let (command, argument) = match line.find(' ') {
+            Some(index) => (&line[..index], line[index..].trim()),
+            None => (line.as_str(), ""),
+        };
+        match (command, argument) {
+            ("station", "") => Trans::Push(Box::new(StationSelectScreen::new())),
+            ("station", name) => {
+                match Self::find_station(ctx, name) {
+                    Some(station) => ctx.player_mut().play(station),
+                    None => ctx.ui().print(&format!("No station named \"{}\"\n", name)),
+                }
+                Trans::None
+            }
+            ("create", "") => Trans::Push(Box::new(StationCreateScreen::new())),
+            ("create", query) => Trans::Push(Box::new(StationCreateScreen::with_query(query))),
+            ("variety", "") => Trans::Push(Box::new(StationAddVarietyScreen::new())),
+            ("variety", query) => {
+                Trans::Push(Box::new(StationAddVarietyScreen::with_query(query)))
+            }
+            ("rename", "") => Trans::Push(Box::new(StationRenameScreen::new())),
+            ("rename", name) => Trans::Push(Box::new(StationRenameScreen::with_name(name))),
+            ("rate", "+") => self.run(ctx, "love"),
+            ("rate", "-") => self.run(ctx, "ban"),
+            ("volume", "") => {
+                let volume = ctx.player().volume();
+                ctx.ui().print(&format!("Volume is {}%\n", volume));
+                Trans::None
+            }
+            ("volume", volume) => {
+                match volume.trim_matches('%').parse::<u8>() {
+                    Ok(volume) => Self::set_volume(ctx, volume),
+                    Err(_) => ctx.ui().print("The volume must be from 0 to 100\n"),
+                }
+                Trans::None
+            }
+            (command, "") if HELP_COMMANDS.iter().any(|&(c, _)| c == command) => {
+                self.run(ctx, command)
+            }
+            _ => {
+                ctx.ui().print(&format!("Unknown command \"{}\"\n", line));
+                Trans::None
+            }
+        }
+    }
+}
+
+impl State for StationScreen {
     fn update(&mut self, ctx: &mut Dobro) -> Trans {
         if let Some(mstatus) = ctx.player().try_next_status() {
             match mstatus {
                 Ok(status) => {
                     match status {
                         PlayerStatus::Standby => {
+                            if self.autoplay {
+                                self.autoplay = false;
+                                if let Some(station) = Self::default_station(ctx) {
+                                    ctx.player_mut().play(station);
+                                    return Trans::None;
+                                }
+                            }
                             return Trans::Push(Box::new(StationSelectScreen::new()));
                         }
 
                         PlayerStatus::Started(station) => {
-                            nc::printw("Type '?' for help.\n");
-                            nc::attron(nc::A_BOLD());
-                            nc::printw(&format!("Station \"{}\"\n", station.station_name));
-                            nc::attroff(nc::A_BOLD());
-                            nc::printw("\n\n");
-                        }
-                        PlayerStatus::Stopped(_) => {
-                            mvrel(-2, 0);
-                        }
-                        PlayerStatus::Fetching(_) => {
-                            mvrel(-2, 0);
-                            nc::printw("Fetching playlist...");
-                            nc::printw("\n\n");
-                        }
-
-                        PlayerStatus::Playing(track) => {
-                            mvrel(-2, 0);
-                            Self::print_song("Playing", &track);
-                            Self::print_progress(ctx);
+                            ctx.saved_mut().set_played(&station.station_id, now());
+                            if ctx.config().help_hint() {
+                                let keys = ctx.config().keymap().keys("help");
+                                if let Some(key) = keys.first() {
+                                    ctx.ui().set_message(&format!("Type '{}' for help.", key));
+                                }
+                            }
+                            ctx.ui().print_bold(&format!("Station \"{}\"\n",
+                                                         station.station_name));
                         }
                         PlayerStatus::Finished(track) => {
-                            mvrel(-2, 0);
-                            Self::print_song("Finished", &track);
-                            nc::printw("\n\n");
-                        }
-                        PlayerStatus::Paused(track) => {
-                            mvrel(-2, 0);
-                            Self::print_song("Paused", &track);
-                            Self::print_progress(ctx);
+                            Self::print_song(ctx, "Played", &track);
                         }
 
                         _ => (),
                     }
                 }
                 Err(err) => {
-                    mvrel(-1, 0);
-                    nc::printw(&format!("ERROR: {}", err));
-                    nc::printw("\n\n");
+                    ctx.ui().print(&format!("ERROR: {}\n", err));
                 }
             }
         }
-        if ctx.player().is_playing() {
-            mvrel(-1, 0);
-            Self::print_progress(ctx);
-        }
-
-        nc::timeout(100);
-        let ch = nc::getch();
-        nc::timeout(-1);
 
-        match ch as u8 as char {
-            '?' => {
-                nc::printw(&format!("{}\n\n\n", HELP_TEXT));
-                ctx.player().report();
-            }
-            'n' => ctx.player_mut().skip(),
-            'p' => ctx.player_mut().toggle_pause(),
-            'c' => return Trans::Push(Box::new(StationCreateScreen::new())),
-            'r' => return Trans::Push(Box::new(StationRenameScreen::new())),
-            'a' => return Trans::Push(Box::new(StationAddVarietyScreen::new())),
-            's' => return Trans::Push(Box::new(StationSelectScreen::new())),
-            'd' => return Trans::Push(Box::new(StationDeleteScreen::new())),
-            rate @ '-' | rate @ '+' => {
-                return Trans::Push(Box::new(TrackRateScreen::new(rate == '+')))
+        let ch = ctx.ui().getch(100);
+        let key = match Key::from_code(ch) {
+            Some(key) => key,
+            None => {
+                self.idle += 1;
+                if self.idle >= SEQUENCE_TIMEOUT {
+                    self.pending.clear();
+                }
+                return Trans::None;
             }
-            'q' => return Trans::Quit,
-            _ => return Trans::None,
         };
+        self.idle = 0;
 
-        Trans::None
+        self.pending.push(key);
+        let command = match ctx.config().keymap().lookup(&self.pending) {
+            Binding::Prefix => return Trans::None,
+            Binding::Command(command) => Some(command.to_owned()),
+            Binding::None => None,
+        };
+        self.pending.clear();
+
+        match command {
+            Some(command) => self.run(ctx, &command),
+            None => Trans::None,
+        }
+    }
+}
+
+/// Returns the candidates that start with the given text, ignoring case and
+/// without repeating any.
+fn complete<'a, I>(text: &str, candidates: I) -> Vec<String>
+    where I: Iterator<Item = &'a str>
+{
+    let text = text.to_lowercase();
+    let mut completions: Vec<String> = Vec::new();
+    for candidate in candidates {
+        if candidate.to_lowercase().starts_with(&text) &&
+           !completions.iter().any(|c| c == candidate) {
+            completions.push(candidate.to_owned());
+        }
     }
+    completions
+}
+
+/// Returns the current time, in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
 }
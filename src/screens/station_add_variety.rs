@@ -2,16 +2,38 @@ use super::super::Dobro;
 
 use state::*;
 
-use screens::station_create::StationMusicScreen;
+use screens::station_create::{MusicSearch, StationMusicScreen};
+use pandora::Station;
 use pandora::music::ToMusicToken;
 
-use ncurses as nc;
-
-pub struct StationAddVarietyScreen {}
+pub struct StationAddVarietyScreen {
+    // Station to add variety to, instead of the playing one.
+    station: Option<Station>,
+    search: MusicSearch,
+}
 
 impl StationAddVarietyScreen {
     pub fn new() -> Self {
-        StationAddVarietyScreen {}
+        StationAddVarietyScreen {
+            station: None,
+            search: MusicSearch::new(),
+        }
+    }
+
+    /// Creates the screen with the results for the given search.
+    pub fn with_query(query: &str) -> Self {
+        StationAddVarietyScreen {
+            station: None,
+            search: MusicSearch::with_query(query),
+        }
+    }
+
+    /// Creates the screen to add variety to the given station.
+    pub fn for_station(station: Station) -> Self {
+        StationAddVarietyScreen {
+            station: Some(station),
+            search: MusicSearch::new(),
+        }
     }
 }
 
@@ -20,17 +42,21 @@ impl StationMusicScreen for StationAddVarietyScreen {
         "Add variety from artist or song: "
     }
 
+    fn search(&mut self) -> &mut MusicSearch {
+        &mut self.search
+    }
+
     fn on_choice<T>(&mut self, ctx: &mut Dobro, music_token: &T)
         where T: ToMusicToken
     {
-        let station = ctx.player().state().station();
+        let station = self.station.clone().or_else(|| ctx.player().state().station());
         if let Some(ref station) = station {
-            nc::printw(&format!("Adding variety to \"{}\"... ", station.station_name));
-            nc::refresh();
-            if let Ok(_) = ctx.pandora().stations().add_seed(station, music_token) {
-                nc::printw("Done\n");
+            ctx.ui().print(&format!("Adding variety to \"{}\"... ", station.station_name));
+            let res = ctx.pandora().stations().add_seed(station, music_token);
+            if let Ok(_) = res {
+                ctx.ui().print("Done\n");
             } else {
-                nc::printw("Unable to add variety to station\n");
+                ctx.ui().print("Unable to add variety to station\n");
             }
         }
     }
@@ -41,7 +67,11 @@ impl State for StationAddVarietyScreen {
         StationMusicScreen::start(self, ctx);
     }
 
-    fn update(&mut self, _ctx: &mut Dobro) -> Trans {
-        Trans::Pop
+    fn stop(&mut self, ctx: &mut Dobro) {
+        StationMusicScreen::stop(self, ctx);
+    }
+
+    fn update(&mut self, ctx: &mut Dobro) -> Trans {
+        StationMusicScreen::update(self, ctx)
     }
 }
@@ -1,19 +1,23 @@
 use super::super::Dobro;
 
+use search::{self, SearchResult};
 use ui::*;
 use state::*;
 
 use pandora::music::ToMusicToken;
 
-use ncurses as nc;
-
-const RESULTS_LENGTH: usize = 3;
-
-pub struct StationCreateScreen {}
+pub struct StationCreateScreen {
+    search: MusicSearch,
+}
 
 impl StationCreateScreen {
     pub fn new() -> Self {
-        StationCreateScreen {}
+        StationCreateScreen { search: MusicSearch::new() }
+    }
+
+    /// Creates the screen with the results for the given search.
+    pub fn with_query(query: &str) -> Self {
+        StationCreateScreen { search: MusicSearch::with_query(query) }
     }
 }
 
@@ -22,16 +26,20 @@ impl StationMusicScreen for StationCreateScreen {
         "Create station from artist or song: "
     }
 
+    fn search(&mut self) -> &mut MusicSearch {
+        &mut self.search
+    }
+
     fn on_choice<T>(&mut self, ctx: &mut Dobro, music_token: &T)
         where T: ToMusicToken
     {
-        nc::printw("Creating station... ");
-        nc::refresh();
-        if let Ok(station) = ctx.pandora().stations().create(music_token) {
-            nc::printw("Done\n");
+        ctx.ui().print("Creating station... ");
+        let res = ctx.pandora().stations().create(music_token);
+        if let Ok(station) = res {
+            ctx.ui().print("Done\n");
             ctx.player_mut().play(station);
         } else {
-            nc::printw("Unable to create station\n");
+            ctx.ui().print("Unable to create station\n");
         }
     }
 }
@@ -41,75 +49,121 @@ impl State for StationCreateScreen {
         StationMusicScreen::start(self, ctx);
     }
 
-    fn update(&mut self, _ctx: &mut Dobro) -> Trans {
-        Trans::Pop
+    fn stop(&mut self, ctx: &mut Dobro) {
+        StationMusicScreen::stop(self, ctx);
+    }
+
+    fn update(&mut self, ctx: &mut Dobro) -> Trans {
+        StationMusicScreen::update(self, ctx)
+    }
+}
+
+/// Results of the last music search, browsed with a [Menu](../ui/struct.Menu.html).
+pub struct MusicSearch {
+    // Search to run when the screen starts, instead of asking for one.
+    query: Option<String>,
+    results: Vec<SearchResult>,
+    menu: Menu,
+}
+
+impl MusicSearch {
+    pub fn new() -> Self {
+        MusicSearch {
+            query: None,
+            results: Vec::new(),
+            menu: Menu::new("", Vec::new()),
+        }
+    }
+
+    pub fn with_query(query: &str) -> Self {
+        let mut search = Self::new();
+        search.query = Some(query.to_owned());
+        search
+    }
+
+    /// Asks for a search string and searches for it.
+    fn prompt(&mut self, ctx: &mut Dobro, message: &str) {
+        if let Some(query) = ctx.ui().prompt(message) {
+            self.find(ctx, &query);
+        }
+    }
+
+    /// Searches for the given string. The previous results are kept when the
+    /// new search fails or finds nothing.
+    fn find(&mut self, ctx: &mut Dobro, query: &str) {
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+
+        ctx.ui().print("Searching... ");
+        match search::search(ctx.pandora(), query) {
+            Ok(ref results) if results.is_empty() => ctx.ui().print("No results\n"),
+            Ok(results) => {
+                ctx.ui().print("Done\n");
+                let items = results.iter()
+                    .map(|result| {
+                             format!("{:<6} {} ({}%)",
+                                     result.kind.name(),
+                                     result.name,
+                                     result.score)
+                         })
+                    .collect();
+                self.menu.set_items(items);
+                self.menu.set_title(&format!("Results for \"{}\" ('s' to search again, \
+                                              '/' to filter)",
+                                             query));
+                self.results = results;
+            }
+            Err(e) => ctx.ui().print(&format!("Unable to search: {}\n", e)),
+        }
     }
 }
 
 pub trait StationMusicScreen {
     fn message(&self) -> &'static str;
 
+    /// Returns the search browsed by the screen.
+    fn search(&mut self) -> &mut MusicSearch;
+
     fn on_choice<T>(&mut self, ctx: &mut Dobro, music_token: &T) where T: ToMusicToken;
 
     fn start(&mut self, ctx: &mut Dobro) {
-        use std::cmp::min;
-
-        nc::attron(nc::A_BOLD());
-        nc::printw(self.message());
-        nc::attroff(nc::A_BOLD());
-        let search_string = getstring();
-        nc::printw("\n");
-
-        nc::printw("Searching... ");
-        nc::refresh();
-        if let Ok(results) = ctx.pandora().music().search(&search_string) {
-
-            let artists_len = min(RESULTS_LENGTH, results.artists().len()) as i32;
-            let songs_len = min(RESULTS_LENGTH, results.songs().len()) as i32;
-
-            if artists_len > 0 || songs_len > 0 {
-                nc::printw("Done\n");
-
-                nc::printw("Artists:\n");
-                for (i, artist) in results.artists().iter().enumerate().take(RESULTS_LENGTH) {
-                    nc::printw(&format!("{} - {}\n", i, artist.artist_name));
-                }
-                nc::printw("Songs:\n");
-                for (i, song) in results.songs().iter().enumerate().take(RESULTS_LENGTH) {
-                    nc::printw(&format!("{} - {} by {}\n",
-                                       i as i32 + artists_len,
-                                       song.song_name,
-                                       song.artist_name));
-                }
-
-                let mut music_token = None;
-                loop {
-                    nc::attron(nc::A_BOLD());
-                    nc::printw("Music choice (blank to cancel): ");
-                    nc::attroff(nc::A_BOLD());
-                    let choice = getchoice();
-                    nc::printw("\n");
-
-                    if choice < 0 {
-                        break;
-                    } else if choice < artists_len {
-                        music_token = Some(results.artists()[choice as usize].to_music_token());
-                        break;
-                    } else if choice < artists_len + songs_len {
-                        music_token = Some(results.songs()[(choice - artists_len) as usize]
-                                               .to_music_token());
-                        break;
-                    }
-                }
-
-                if let Some(ref music_token) = music_token {
-                    self.on_choice(ctx, music_token);
-                }
-            } else {
-                nc::printw("No results\n");
+        match self.search().query.take() {
+            Some(query) => self.search().find(ctx, &query),
+            None => {
+                let message = self.message();
+                self.search().prompt(ctx, message);
             }
-        } else {
-            nc::printw("Error\n");
         }
     }
+
+    fn stop(&mut self, ctx: &mut Dobro) {
+        ctx.ui().redraw_history();
+    }
+
+    fn update(&mut self, ctx: &mut Dobro) -> Trans {
+        // Nothing was found by the first search.
+        if self.search().results.is_empty() {
+            return Trans::Pop;
+        }
+
+        let ch = ctx.ui().getch(100);
+        match self.search().menu.key(ch) {
+            MenuEvent::Choose(index) => {
+                let result = self.search().results[index].clone();
+                self.on_choice(ctx, &result);
+                return Trans::Pop;
+            }
+            MenuEvent::Cancel => return Trans::Pop,
+            MenuEvent::Key(ch) if ch == 's' as i32 => {
+                let message = self.message();
+                self.search().prompt(ctx, message);
+            }
+            _ => (),
+        }
+
+        ctx.ui().draw_menu(&mut self.search().menu);
+        Trans::None
+    }
 }
@@ -1,29 +1,90 @@
 use super::super::Dobro;
 
+use stations::{self, DeletedStation};
 use state::*;
 
-use ncurses as nc;
+use pandora::Station;
 
-pub struct StationDeleteScreen {}
+pub struct StationDeleteScreen {
+    // Station to delete, instead of the playing one.
+    station: Option<Station>,
+}
 
 impl StationDeleteScreen {
     pub fn new() -> Self {
-        StationDeleteScreen {}
+        StationDeleteScreen { station: None }
+    }
+
+    /// Creates the screen to delete the given station.
+    pub fn for_station(station: Station) -> Self {
+        StationDeleteScreen { station: Some(station) }
+    }
+
+    /// Asks to confirm the deletion of the station with the given name, by
+    /// typing "y" or the name itself as set in the user configuration.
+    fn confirm(ctx: &mut Dobro, name: &str, seeds: &str) -> bool {
+        if ctx.config().confirm_by_name() {
+            let message = format!("Type \"{}\" ({}) to delete it: ", name, seeds);
+            ctx.ui().prompt(&message).map_or(false, |input| input.trim() == name)
+        } else {
+            let message = format!("Delete \"{}\" ({})? [y/N] ", name, seeds);
+            ctx.ui().prompt(&message).map_or(false, |input| {
+                let input = input.trim().to_lowercase();
+                input == "y" || input == "yes"
+            })
+        }
     }
 }
 
 impl State for StationDeleteScreen {
     fn start(&mut self, ctx: &mut Dobro) {
-        let station = ctx.player().state().station().clone();
+        let station = self.station.take().or_else(|| ctx.player().state().station());
         if let Some(station) = station {
-            nc::printw(&format!("Deleting \"{}\"... ", station.station_name));
-            nc::refresh();
+            // The seeds are kept to create the station again.
+            ctx.ui().print("Fetching the station seeds... ");
+            let seeds = match stations::seeds(ctx.pandora(), &station) {
+                Ok(seeds) => {
+                    ctx.ui().print("Done\n");
+                    Some(seeds)
+                }
+                Err(e) => {
+                    ctx.ui().print(&format!("Unable to fetch them, the station can't be created \
+                                             again once deleted: {}\n",
+                                            e));
+                    None
+                }
+            };
+            let count = match seeds {
+                Some(ref seeds) if seeds.len() == 1 => "1 seed".to_owned(),
+                Some(ref seeds) => format!("{} seeds", seeds.len()),
+                None => "unknown seeds".to_owned(),
+            };
+
+            if !Self::confirm(ctx, &station.station_name, &count) {
+                ctx.ui().print(&format!("Keeping \"{}\"\n", station.station_name));
+                return;
+            }
+
+            ctx.ui().print(&format!("Deleting \"{}\"... ", station.station_name));
 
             if let Ok(_) = ctx.pandora().stations().delete(&station) {
-                nc::printw("Done\n");
-                ctx.player_mut().stop();
+                match seeds {
+                    Some(seeds) if !seeds.is_empty() => {
+                        ctx.ui().print("Done, :undo creates it again\n");
+                        ctx.deleted_mut().push(DeletedStation {
+                                                   name: station.station_name.clone(),
+                                                   seeds: seeds,
+                                               });
+                    }
+                    _ => ctx.ui().print("Done\n"),
+                }
+                // Other stations keep playing.
+                let playing = ctx.player().state().station();
+                if playing.map(|playing| playing.station_id) == Some(station.station_id.clone()) {
+                    ctx.player_mut().stop();
+                }
             } else {
-                nc::printw("Unable to delete\n");
+                ctx.ui().print("Unable to delete\n");
             }
         }
     }
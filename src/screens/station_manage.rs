@@ -0,0 +1,107 @@
+use super::super::Dobro;
+use super::{StationAddVarietyScreen, StationDeleteScreen, StationRenameScreen};
+
+use stations;
+use ui::*;
+use state::*;
+
+use pandora::Station;
+
+/// List of the user stations, where any of them can be renamed, deleted or
+/// given more variety without changing the playing station.
+pub struct StationManageScreen {
+    // Fetched on the first update, and again after every change.
+    stations: Option<Vec<Station>>,
+    // Station selected before the list was fetched again.
+    selected: Option<String>,
+    menu: Menu,
+}
+
+impl StationManageScreen {
+    pub fn new() -> Self {
+        StationManageScreen {
+            stations: None,
+            selected: None,
+            menu: Menu::new("Manage stations ('r' to rename, 'd' to delete, 'a' to add \
+                             variety, '/' to filter)",
+                            Vec::new()),
+        }
+    }
+
+    /// Returns the selected station, if any station matches the filter.
+    fn selected(&self) -> Option<Station> {
+        match (self.stations.as_ref(), self.menu.selected()) {
+            (Some(stations), Some(index)) => Some(stations[index].clone()),
+            _ => None,
+        }
+    }
+}
+
+impl State for StationManageScreen {
+    fn stop(&mut self, ctx: &mut Dobro) {
+        ctx.ui().redraw_history();
+    }
+
+    fn pause(&mut self, _ctx: &mut Dobro) {
+        self.selected = self.selected().map(|station| station.station_id);
+    }
+
+    fn resume(&mut self, _ctx: &mut Dobro) {
+        // The station might have been renamed or deleted.
+        self.stations = None;
+    }
+
+    fn update(&mut self, ctx: &mut Dobro) -> Trans {
+        if self.stations.is_none() {
+            ctx.ui().print("Fetching Stations... ");
+            match stations::list(ctx.pandora()) {
+                Ok(list) => {
+                    ctx.ui().print("Done\n");
+                    let mut list: Vec<Station> =
+                        list.into_iter().map(|info| info.station).collect();
+                    list.sort_by_key(|station| station.station_name.to_lowercase());
+
+                    let names = list.iter().map(|station| station.station_name.clone()).collect();
+                    self.menu.set_items(names);
+                    let current = ctx.player().state().station();
+                    let current = current.and_then(|current| {
+                        list.iter().position(|station| station.station_id == current.station_id)
+                    });
+                    self.menu.set_marked(current);
+                    let selected = self.selected.take().and_then(|selected| {
+                        list.iter().position(|station| station.station_id == selected)
+                    });
+                    if let Some(index) = selected {
+                        self.menu.select(index);
+                    }
+                    self.stations = Some(list);
+                }
+                Err(e) => {
+                    ctx.ui().print(&format!("Unable to fetch the stations: {}\n", e));
+                    return Trans::Pop;
+                }
+            }
+        }
+
+        let ch = ctx.ui().getch(100);
+        match self.menu.key(ch) {
+            MenuEvent::Cancel => return Trans::Pop,
+            MenuEvent::Key(ch) if ch == 'r' as i32 || ch == 'd' as i32 || ch == 'a' as i32 => {
+                if let Some(station) = self.selected() {
+                    let screen: Box<State> = match ch as u8 as char {
+                        'r' => Box::new(StationRenameScreen::for_station(station)),
+                        'd' => Box::new(StationDeleteScreen::for_station(station)),
+                        _ => Box::new(StationAddVarietyScreen::for_station(station)),
+                    };
+                    // The history shows what the screen prints.
+                    ctx.ui().redraw_history();
+                    return Trans::Push(screen);
+                }
+            }
+            _ => (),
+        }
+
+        ctx.ui().draw_menu(&mut self.menu);
+        Trans::None
+    }
+}
@@ -1,44 +1,71 @@
 use super::super::Dobro;
 
-use ui::*;
 use state::*;
 
-use ncurses as nc;
+use pandora::Station;
 
-pub struct StationRenameScreen {}
+pub struct StationRenameScreen {
+    // Station to rename, instead of the playing one.
+    station: Option<Station>,
+    // New name to use, instead of asking for one.
+    name: Option<String>,
+}
 
 impl StationRenameScreen {
     pub fn new() -> Self {
-        StationRenameScreen {}
+        StationRenameScreen {
+            station: None,
+            name: None,
+        }
+    }
+
+    /// Creates the screen to rename the playing station to the given name.
+    pub fn with_name(name: &str) -> Self {
+        StationRenameScreen {
+            station: None,
+            name: Some(name.to_owned()),
+        }
+    }
+
+    /// Creates the screen to rename the given station.
+    pub fn for_station(station: Station) -> Self {
+        StationRenameScreen {
+            station: Some(station),
+            name: None,
+        }
     }
 }
 
 impl State for StationRenameScreen {
     fn start(&mut self, ctx: &mut Dobro) {
-        let station = ctx.player().state().station();
+        let station = self.station.take().or_else(|| ctx.player().state().station());
         if let Some(station) = station {
-            nc::attron(nc::A_BOLD());
-            nc::printw(&format!("Renaming station \"{}\"\n", station.station_name));
-            nc::attroff(nc::A_BOLD());
+            ctx.ui().print_bold(&format!("Renaming station \"{}\"\n", station.station_name));
 
-            nc::printw("New name (blank to cancel): ");
-            let new_name = getstring().trim().to_owned();
-            nc::printw("\n");
+            let new_name = match self.name.take() {
+                Some(name) => name,
+                None => ctx.ui().prompt("New name (blank to cancel): ").unwrap_or_default(),
+            };
+            let new_name = new_name.trim().to_owned();
 
             if new_name.len() > 0 {
-                nc::printw("Renaming... ");
-                nc::refresh();
-
-                if let Ok(_) = ctx.pandora().stations().rename(&station, &new_name) {
-                    nc::printw(&format!("Renamed station to \"{}\"\n", new_name));
-                    // if let Some(ref mut st) = ctx.player_mut().state().station {
-                    //     st.station_name = new_name;
-                    // }
+                ctx.ui().print("Renaming... ");
+
+                let res = ctx.pandora().stations().rename(&station, &new_name);
+                if let Ok(renamed) = res {
+                    ctx.ui().print(&format!("Renamed station to \"{}\"\n", new_name));
+                    // The header shows the name of the playing station.
+                    let mut state = ctx.player().state();
+                    let playing = state.station()
+                        .map_or(false, |playing| playing.station_id == renamed.station_id);
+                    if playing {
+                        state.set_station(renamed);
+                    }
                 } else {
-                    nc::printw(&format!("Unable to use the name \"{}\"\n", &new_name));
+                    ctx.ui().print(&format!("Unable to use the name \"{}\"\n", &new_name));
                 }
             } else {
-                nc::printw("Leaving old name\n");
+                ctx.ui().print("Leaving old name\n");
             }
         }
     }
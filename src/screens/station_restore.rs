@@ -0,0 +1,70 @@
+use super::super::Dobro;
+
+use state::*;
+
+/// Creates the last station deleted in this session again, and plays it.
+pub struct StationRestoreScreen {}
+
+impl StationRestoreScreen {
+    pub fn new() -> Self {
+        StationRestoreScreen {}
+    }
+}
+
+impl State for StationRestoreScreen {
+    fn start(&mut self, ctx: &mut Dobro) {
+        let deleted = match ctx.deleted_mut().pop() {
+            Some(deleted) => deleted,
+            None => {
+                ctx.ui().print("No deleted station to create again\n");
+                return;
+            }
+        };
+
+        let first = match deleted.seeds.first() {
+            Some(seed) => seed.clone(),
+            None => {
+                ctx.ui().print(&format!("\"{}\" has no seeds to create it from\n",
+                                        deleted.name));
+                return;
+            }
+        };
+
+        // Every call is retried on its own, so a failure past the creation
+        // doesn't create the station twice.
+        ctx.ui().print(&format!("Creating \"{}\" again with {} seed(s)... ",
+                                deleted.name,
+                                deleted.seeds.len()));
+        let station = match ctx.pandora().stations().create(&first) {
+            Ok(station) => station,
+            Err(e) => {
+                ctx.ui().print(&format!("Unable to create it: {}\n", e));
+                ctx.deleted_mut().push(deleted);
+                return;
+            }
+        };
+        ctx.ui().print("Done\n");
+
+        // The station exists from here on, so it's played even if it only gets
+        // some of its seeds or keeps the name of its first one.
+        for seed in &deleted.seeds[1..] {
+            let added = ctx.pandora().stations().add_seed(&station, seed);
+            if let Err(e) = added {
+                ctx.ui().print(&format!("Unable to add \"{}\": {}\n", seed.name, e));
+            }
+        }
+        let renamed = ctx.pandora().stations().rename(&station, &deleted.name);
+        let station = match renamed {
+            Ok(renamed) => renamed,
+            Err(e) => {
+                ctx.ui().print(&format!("Unable to rename it: {}\n", e));
+                station
+            }
+        };
+        ctx.player_mut().play(station);
+    }
+
+    fn update(&mut self, _ctx: &mut Dobro) -> Trans {
+        Trans::Pop
+    }
+}
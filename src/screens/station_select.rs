@@ -1,57 +1,135 @@
 use super::super::Dobro;
 use super::StationCreateScreen;
 
+use stations::{self, StationInfo};
 use ui::*;
 use state::*;
 
-use ncurses as nc;
+/// Order of the station list.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Sort {
+    Name,
+    Played,
+    Created,
+}
 
-pub struct StationSelectScreen {}
+impl Sort {
+    fn name(&self) -> &'static str {
+        match *self {
+            Sort::Name => "name",
+            Sort::Played => "recently played",
+            Sort::Created => "date created",
+        }
+    }
 
-impl StationSelectScreen {
-    pub fn new() -> Self {
-        StationSelectScreen {}
+    fn next(&self) -> Self {
+        match *self {
+            Sort::Name => Sort::Played,
+            Sort::Played => Sort::Created,
+            Sort::Created => Sort::Name,
+        }
     }
 }
 
-impl State for StationSelectScreen {
-    fn update(&mut self, ctx: &mut Dobro) -> Trans {
-        nc::printw("Fetching Stations... ");
-        nc::refresh();
+pub struct StationSelectScreen {
+    // Fetched on the first update.
+    stations: Option<Vec<StationInfo>>,
+    sort: Sort,
+    menu: Menu,
+}
 
-        let stations = ctx.pandora().stations().list().unwrap();
+impl StationSelectScreen {
+    pub fn new() -> Self {
+        StationSelectScreen {
+            stations: None,
+            sort: Sort::Name,
+            menu: Menu::new("", Vec::new()),
+        }
+    }
 
-        nc::printw("Done\n");
+    /// Sorts the stations and shows them in the menu, selecting the playing one.
+    fn update_menu(&mut self, ctx: &Dobro) {
+        let stations = match self.stations {
+            Some(ref mut stations) => stations,
+            None => return,
+        };
+        match self.sort {
+            Sort::Name => {
+                stations.sort_by_key(|info| info.station.station_name.to_lowercase());
+            }
+            Sort::Played => {
+                let saved = ctx.saved();
+                stations.sort_by(|a, b| {
+                                     saved.played(&b.station.station_id)
+                                         .cmp(&saved.played(&a.station.station_id))
+                                 });
+            }
+            Sort::Created => stations.sort_by(|a, b| b.date_created.cmp(&a.date_created)),
+        }
 
-        nc::attron(nc::A_BOLD());
-        nc::printw("Stations\n");
-        nc::attroff(nc::A_BOLD());
+        self.menu.set_title(&format!("Stations, sorted by {} ('o' to change, '/' to filter)",
+                                     self.sort.name()));
+        let names = stations.iter().map(|info| info.station.station_name.clone()).collect();
+        self.menu.set_items(names);
 
-        if stations.len() <= 0 {
-            return Trans::Push(Box::new(StationCreateScreen::new()));
-        } else {
-            for (index, station) in stations.iter().enumerate() {
-                nc::printw(&format!("{} - {}\n", index, station.station_name));
-            }
+        let current = ctx.player().state().station();
+        let current = current.and_then(|current| {
+            stations.iter().position(|info| info.station.station_id == current.station_id)
+        });
+        self.menu.set_marked(current);
+        if let Some(index) = current {
+            self.menu.select(index);
+        }
+    }
+}
+
+impl State for StationSelectScreen {
+    fn stop(&mut self, ctx: &mut Dobro) {
+        ctx.ui().redraw_history();
+    }
 
-            let mut choice;
-            loop {
-                nc::attron(nc::A_BOLD());
-                nc::printw("Station choice (blank to cancel): ");
-                nc::attroff(nc::A_BOLD());
-                choice = getchoice();
-                nc::printw("\n");
-
-                if choice >= 0 && choice < stations.len() as i32 {
-                    break;
-                } else if choice < 0 {
+    fn update(&mut self, ctx: &mut Dobro) -> Trans {
+        if self.stations.is_none() {
+            ctx.ui().print("Fetching Stations... ");
+            match stations::list(ctx.pandora()) {
+                Ok(stations) => {
+                    ctx.ui().print("Done\n");
+                    if stations.is_empty() {
+                        return Trans::Replace(Box::new(StationCreateScreen::new()));
+                    }
+                    self.stations = Some(stations);
+                    self.update_menu(ctx);
+                }
+                Err(e) => {
+                    ctx.ui().print(&format!("Unable to fetch the stations: {}\n", e));
                     return Trans::Pop;
                 }
             }
+        }
 
-            ctx.player_mut().play(stations[choice as usize].clone());
+        let ch = ctx.ui().getch(100);
+        match self.menu.key(ch) {
+            MenuEvent::Choose(index) => {
+                let station = match self.stations {
+                    Some(ref stations) => stations[index].station.clone(),
+                    None => return Trans::Pop,
+                };
+                // Choosing the playing station keeps it playing.
+                let current = ctx.player().state().station();
+                if current.map(|current| current.station_id) != Some(station.station_id.clone()) {
+                    ctx.player_mut().play(station);
+                }
+                return Trans::Pop;
+            }
+            MenuEvent::Cancel => return Trans::Pop,
+            MenuEvent::Key(ch) if ch == 'o' as i32 => {
+                self.sort = self.sort.next();
+                self.update_menu(ctx);
+            }
+            _ => (),
         }
 
-        Trans::Pop
+        ctx.ui().draw_menu(&mut self.menu);
+        Trans::None
     }
 }
@@ -1,10 +1,7 @@
 use super::super::Dobro;
 
-use ui::*;
 use state::*;
 
-use ncurses as nc;
-
 pub struct TrackRateScreen {
     is_positive: bool,
 }
@@ -21,25 +18,22 @@ impl State for TrackRateScreen {
         let track = ctx.player().state().track();
         if let Some(station) = station {
             if let Some(track) = track {
-                mvrel(-1, 0);
-                nc::printw("Rating track... ");
-                nc::refresh();
+                ctx.ui().print("Rating track... ");
 
+                let is_positive = self.is_positive;
                 let res = ctx.pandora()
                     .stations()
                     .playlist(&station)
-                    .rate(track, self.is_positive);
+                    .rate(&track, is_positive);
                 match res {
                     Ok(_) => {
-                        nc::printw("Done\n");
+                        ctx.ui().print("Done\n");
                         if !self.is_positive {
                             ctx.player_mut().skip();
-                        } else {
-                            ctx.player().report();
                         }
                     }
                     _ => {
-                        nc::printw("Error\n");
+                        ctx.ui().print("Error\n");
                     }
                 };
             }
@@ -0,0 +1,93 @@
+//! Music search, including the genre stations. Pandora sends them along with the
+//! artists and songs, but the pandora crate doesn't deserialize them.
+
+use pandora::Pandora;
+use pandora::error::Result;
+use pandora::method::Method;
+use pandora::music::{Artist, Song, ToMusicToken};
+
+use serde_json::{self, Value};
+use serde_json::builder::ObjectBuilder;
+
+/// What a search result is.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ResultKind {
+    Artist,
+    Song,
+    Genre,
+}
+
+impl ResultKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ResultKind::Artist => "Artist",
+            ResultKind::Song => "Song",
+            ResultKind::Genre => "Genre",
+        }
+    }
+}
+
+/// Artist, song or genre station matching a search, usable as a station seed.
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub kind: ResultKind,
+    /// Name of the artist or genre station, or "<song> by <artist>".
+    pub name: String,
+    pub music_token: String,
+    /// How well the result matches the search, from 0 to 100.
+    pub score: u32,
+}
+
+impl ToMusicToken for SearchResult {
+    fn to_music_token(&self) -> String {
+        self.music_token.clone()
+    }
+}
+
+/// Searches Pandora for the given text. Artists come first, then songs, then
+/// genre stations.
+pub fn search(pandora: &Pandora, text: &str) -> Result<Vec<SearchResult>> {
+    let body = ObjectBuilder::new()
+        .insert("searchText", text)
+        .insert("includeNearMatches", true)
+        .insert("includeGenreStations", true)
+        .build();
+    let results = try!(pandora.post::<Value>(Method::MusicSearch, Some(body)));
+
+    let mut list = Vec::new();
+    for item in array(&results, "artists") {
+        let artist = try!(serde_json::from_value::<Artist>(item));
+        list.push(SearchResult {
+                      kind: ResultKind::Artist,
+                      name: artist.artist_name,
+                      music_token: artist.music_token,
+                      score: artist.score,
+                  });
+    }
+    for item in array(&results, "songs") {
+        let song = try!(serde_json::from_value::<Song>(item));
+        list.push(SearchResult {
+                      kind: ResultKind::Song,
+                      name: format!("{} by {}", song.song_name, song.artist_name),
+                      music_token: song.music_token,
+                      score: song.score,
+                  });
+    }
+    for item in array(&results, "genreStations") {
+        let name = item.find("stationName").and_then(Value::as_str);
+        let token = item.find("musicToken").and_then(Value::as_str);
+        if let (Some(name), Some(token)) = (name, token) {
+            list.push(SearchResult {
+                          kind: ResultKind::Genre,
+                          name: name.to_owned(),
+                          music_token: token.to_owned(),
+                          score: item.find("score").and_then(Value::as_u64).unwrap_or(0) as u32,
+                      });
+        }
+    }
+    Ok(list)
+}
+
+fn array(value: &Value, key: &str) -> Vec<Value> {
+    value.find(key).and_then(Value::as_array).cloned().unwrap_or_default()
+}
@@ -0,0 +1,109 @@
+//! User stations along with their creation date and seeds. Pandora sends them
+//! with the stations, but the pandora crate doesn't deserialize them.
+
+use pandora::{Pandora, Station};
+use pandora::error::Result;
+use pandora::method::Method;
+use pandora::music::ToMusicToken;
+use pandora::stations::ToStationToken;
+
+use serde_json::{self, Value};
+use serde_json::builder::ObjectBuilder;
+
+/// Station from the user station list.
+#[derive(Debug, Clone)]
+pub struct StationInfo {
+    pub station: Station,
+    /// Creation time, in milliseconds since the Unix epoch.
+    pub date_created: Option<u64>,
+}
+
+/// Gets the user station list from Pandora.
+pub fn list(pandora: &Pandora) -> Result<Vec<StationInfo>> {
+    let list = try!(pandora.post::<Value>(Method::UserGetStationList, None));
+
+    let items = list.find("stations").and_then(Value::as_array).cloned().unwrap_or_default();
+    let mut stations = Vec::with_capacity(items.len());
+    for item in items {
+        let date_created = item.find("dateCreated")
+            .and_then(|date| date.find("time"))
+            .and_then(Value::as_u64);
+        stations.push(StationInfo {
+                          station: try!(serde_json::from_value::<Station>(item)),
+                          date_created: date_created,
+                      });
+    }
+    Ok(stations)
+}
+
+/// Artist, song or genre a station is based on.
+#[derive(Debug, Clone)]
+pub struct StationSeed {
+    /// Name of the artist or genre, or "<song> by <artist>".
+    pub name: String,
+    pub music_token: String,
+}
+
+impl ToMusicToken for StationSeed {
+    fn to_music_token(&self) -> String {
+        self.music_token.clone()
+    }
+}
+
+/// Station deleted by the user, with what is needed to create it again.
+#[derive(Debug, Clone)]
+pub struct DeletedStation {
+    pub name: String,
+    pub seeds: Vec<StationSeed>,
+}
+
+/// Gets the seeds of the given station from Pandora. Stations without seeds
+/// (e.g. the shuffle station) get an empty list.
+pub fn seeds<T>(pandora: &Pandora, station: &T) -> Result<Vec<StationSeed>>
+    where T: ToStationToken
+{
+    let body = ObjectBuilder::new()
+        .insert("stationToken", station.to_station_token())
+        .insert("includeExtendedAttributes", true)
+        .build();
+    let station = try!(pandora.post::<Value>(Method::StationGetStation, Some(body)));
+
+    let music = match station.find("music") {
+        Some(music) => music,
+        None => return Ok(Vec::new()),
+    };
+    let mut seeds = Vec::new();
+    for item in array(music, "artists") {
+        seeds.push(seed(item, item.find("artistName").and_then(Value::as_str)));
+    }
+    for item in array(music, "songs") {
+        let song = item.find("songName").and_then(Value::as_str);
+        let artist = item.find("artistName").and_then(Value::as_str);
+        let name = match (song, artist) {
+            (Some(song), Some(artist)) => Some(format!("{} by {}", song, artist)),
+            _ => None,
+        };
+        seeds.push(seed(item, name.as_ref().map(|name| name.as_str())));
+    }
+    for item in array(music, "genres") {
+        seeds.push(seed(item, item.find("genreName").and_then(Value::as_str)));
+    }
+    Ok(seeds.into_iter().filter_map(|seed| seed).collect())
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value.find(key).and_then(Value::as_array).map(|items| items.as_slice()).unwrap_or(&[])
+}
+
+fn seed(item: &Value, name: Option<&str>) -> Option<StationSeed> {
+    let token = item.find("musicToken").and_then(Value::as_str);
+    match (name, token) {
+        (Some(name), Some(token)) => {
+            Some(StationSeed {
+                     name: name.to_owned(),
+                     music_token: token.to_owned(),
+                 })
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,346 @@
+//! Full-screen layout of the application. The screen is split in four panes:
+//!
+//! * a header with the station being played,
+//! * the track being played along with its progress,
+//! * a scrolling history of the messages printed by the screens,
+//! * a status line at the bottom, used for prompts and key input.
+
+use super::{LineEditor, Menu};
+
+use config::{Config, TimeFormat};
+use player::{PlayerState, PlayerStatus};
+
+use ncurses as nc;
+
+use std::collections::{BTreeMap, VecDeque};
+
+/// Height of the now playing pane, including its bottom border.
+const NOW_PLAYING_HEIGHT: i32 = 5;
+
+/// Narrowest progress bar drawn, narrower terminals only get the times.
+const MIN_BAR_WIDTH: usize = 10;
+
+/// Number of lines kept in the history, to redraw it after a resize.
+const HISTORY_LENGTH: usize = 500;
+
+/// Windows of the layout and the content needed to redraw them.
+pub struct Layout {
+    header: nc::WINDOW,
+    now_playing: nc::WINDOW,
+    history: nc::WINDOW,
+    status: nc::WINDOW,
+
+    // Lines printed to the history, the last one is the line being printed.
+    lines: VecDeque<String>,
+
+    // Message shown on the status line when there's no prompt.
+    message: String,
+
+    // Editors of the prompts, by message, to keep a history for each of them.
+    editors: BTreeMap<String, LineEditor>,
+
+    // How the track progress is drawn.
+    time_format: TimeFormat,
+    progress_bar: bool,
+}
+
+impl Layout {
+    /// Creates the windows for the whole screen, the progress of the tracks is
+    /// drawn as set in the given configuration. ncurses must be initialized.
+    pub fn new(config: &Config) -> Self {
+        let mut layout = Layout {
+            header: nc::newwin(1, 1, 0, 0),
+            now_playing: nc::newwin(1, 1, 0, 0),
+            history: nc::newwin(1, 1, 0, 0),
+            status: nc::newwin(1, 1, 0, 0),
+            lines: VecDeque::with_capacity(HISTORY_LENGTH),
+            message: String::new(),
+            editors: BTreeMap::new(),
+            time_format: config.time_format(),
+            progress_bar: config.progress_bar(),
+        };
+        layout.lines.push_back(String::new());
+        layout.resize();
+        layout
+    }
+
+    /// Recreates the windows for the current terminal size, and redraws the
+    /// history. The other panes are redrawn with the next call to
+    /// [draw](#method.draw).
+    pub fn resize(&mut self) {
+        let mut lines = 0;
+        let mut cols = 0;
+        nc::getmaxyx(nc::stdscr(), &mut lines, &mut cols);
+
+        for window in &[self.header, self.now_playing, self.history, self.status] {
+            nc::delwin(*window);
+        }
+
+        let history_height = (lines - NOW_PLAYING_HEIGHT - 2).max(1);
+        self.header = nc::newwin(1, cols, 0, 0);
+        self.now_playing = nc::newwin(NOW_PLAYING_HEIGHT, cols, 1, 0);
+        self.history = nc::newwin(history_height, cols, 1 + NOW_PLAYING_HEIGHT, 0);
+        self.status = nc::newwin(1, cols, lines - 1, 0);
+        nc::scrollok(self.history, true);
+        nc::wbkgd(self.header, nc::A_REVERSE());
+
+        nc::clear();
+        nc::refresh();
+        self.redraw_history();
+    }
+
+    /// Redraws the history pane from the printed lines, e.g. after a menu was
+    /// drawn over it.
+    pub fn redraw_history(&mut self) {
+        let mut lines = 0;
+        let mut cols = 0;
+        nc::getmaxyx(self.history, &mut lines, &mut cols);
+        nc::werase(self.history);
+
+        // Only the last lines can be visible, even without wrapping.
+        let skip = self.lines.len().saturating_sub(lines as usize);
+        let last = self.lines.len() - 1;
+        for (index, line) in self.lines.iter().enumerate().skip(skip) {
+            nc::waddstr(self.history, line);
+            if index < last {
+                nc::waddstr(self.history, "\n");
+            }
+        }
+        nc::wnoutrefresh(self.history);
+    }
+
+    /// Draws the given menu over the history pane.
+    pub fn draw_menu(&mut self, menu: &mut Menu) {
+        menu.draw(self.history);
+        nc::wnoutrefresh(self.history);
+    }
+
+    /// Prints the given text to the history.
+    pub fn print(&mut self, text: &str) {
+        for (index, part) in text.split('\n').enumerate() {
+            if index > 0 {
+                if self.lines.len() >= HISTORY_LENGTH {
+                    self.lines.pop_front();
+                }
+                self.lines.push_back(String::new());
+            }
+            if let Some(line) = self.lines.back_mut() {
+                line.push_str(part);
+            }
+        }
+
+        nc::waddstr(self.history, text);
+        nc::wrefresh(self.history);
+    }
+
+    /// Prints the given text to the history in bold.
+    pub fn print_bold(&mut self, text: &str) {
+        nc::wattron(self.history, nc::A_BOLD());
+        self.print(text);
+        nc::wattroff(self.history, nc::A_BOLD());
+    }
+
+    /// Sets the message shown on the status line.
+    pub fn set_message(&mut self, message: &str) {
+        self.message = message.to_owned();
+    }
+
+    /// Asks for a line of input on the status line, see
+    /// [LineEditor](struct.LineEditor.html). Returns `None` when the prompt is
+    /// cancelled.
+    pub fn prompt(&mut self, message: &str) -> Option<String> {
+        nc::werase(self.status);
+        nc::wattron(self.status, nc::A_BOLD());
+        nc::waddstr(self.status, message);
+        nc::wattroff(self.status, nc::A_BOLD());
+        self.read_line(message, |_| Vec::new())
+    }
+
+    /// Asks for a command on the status line, where tab cycles through the
+    /// completions given by `complete`.
+    pub fn prompt_command<F>(&mut self, message: &str, complete: F) -> Option<String>
+        where F: FnMut(&str) -> Vec<String>
+    {
+        nc::werase(self.status);
+        nc::waddstr(self.status, message);
+        self.read_line(message, complete)
+    }
+
+    /// Waits for a key at most `timeout` milliseconds (forever if negative).
+    /// Returns `nc::ERR` when no key was pressed. The layout is resized when
+    /// the terminal is.
+    pub fn getch(&mut self, timeout: i32) -> i32 {
+        nc::wtimeout(self.status, timeout);
+        nc::keypad(self.status, true);
+        let ch = nc::wgetch(self.status);
+        nc::keypad(self.status, false);
+        nc::wtimeout(self.status, -1);
+
+        if ch == nc::KEY_RESIZE {
+            self.resize();
+        }
+        ch
+    }
+
+    /// Reads a line after the prompt with the given message, and resizes the
+    /// layout if the terminal was resized meanwhile.
+    fn read_line<F>(&mut self, message: &str, complete: F) -> Option<String>
+        where F: FnMut(&str) -> Vec<String>
+    {
+        let mut lines = 0;
+        let mut cols = 0;
+        nc::getmaxyx(nc::stdscr(), &mut lines, &mut cols);
+
+        let input = self.editors
+            .entry(message.to_owned())
+            .or_insert_with(LineEditor::new)
+            .read_with(self.status, complete);
+        nc::werase(self.status);
+        nc::wrefresh(self.status);
+
+        let mut new_lines = 0;
+        let mut new_cols = 0;
+        nc::getmaxyx(nc::stdscr(), &mut new_lines, &mut new_cols);
+        if (new_lines, new_cols) != (lines, cols) {
+            self.resize();
+        }
+        input
+    }
+
+    /// Draws the header, now playing pane and status line for the given player
+    /// state.
+    pub fn draw(&mut self, state: &PlayerState) {
+        self.draw_header(state);
+        self.draw_now_playing(state);
+
+        nc::werase(self.status);
+        nc::waddstr(self.status, &self.message);
+        nc::wnoutrefresh(self.status);
+
+        nc::doupdate();
+    }
+
+    fn draw_header(&self, state: &PlayerState) {
+        nc::werase(self.header);
+        nc::wattron(self.header, nc::A_BOLD());
+        nc::waddstr(self.header, " Dobro");
+        nc::wattroff(self.header, nc::A_BOLD());
+        if let Some(station) = state.station() {
+            nc::waddstr(self.header, &format!("  Station \"{}\"", station.station_name));
+        }
+
+        let volume = if state.is_muted() {
+            "Muted".to_owned()
+        } else {
+            format!("Volume {}%", state.volume())
+        };
+        let info = format!("{}  {} quality ", volume, state.quality().name());
+        let mut cols = 0;
+        let mut lines = 0;
+        nc::getmaxyx(self.header, &mut lines, &mut cols);
+        let x = cols - info.chars().count() as i32;
+        if x > 0 {
+            nc::mvwaddstr(self.header, 0, x, &info);
+        }
+        nc::wnoutrefresh(self.header);
+    }
+
+    fn draw_now_playing(&self, state: &PlayerState) {
+        let mut cols = 0;
+        let mut lines = 0;
+        nc::getmaxyx(self.now_playing, &mut lines, &mut cols);
+        nc::werase(self.now_playing);
+
+        let status = match state.status() {
+            PlayerStatus::Playing(_) => "Playing",
+            PlayerStatus::Paused(_) => "Paused",
+            PlayerStatus::Fetching(_) => "Fetching playlist...",
+            PlayerStatus::Started(_) => "Starting...",
+            _ => "Stopped",
+        };
+        nc::mvwaddstr(self.now_playing, 0, 1, status);
+
+        let track = match state.status() {
+            PlayerStatus::Playing(_) |
+            PlayerStatus::Paused(_) => state.track(),
+            _ => None,
+        };
+        if let Some(track) = track {
+            let unknown = "Unknown".to_owned();
+            let loved = track.song_rating.unwrap_or(0) > 0;
+            nc::waddstr(self.now_playing, " ");
+            nc::wattron(self.now_playing, nc::A_BOLD());
+            nc::waddstr(self.now_playing,
+                        &format!("\"{}\"", track.song_name.as_ref().unwrap_or(&unknown)));
+            nc::wattroff(self.now_playing, nc::A_BOLD());
+            if loved {
+                nc::waddstr(self.now_playing, "  <3");
+            }
+            nc::mvwaddstr(self.now_playing,
+                          1,
+                          1,
+                          &format!("by {}", track.artist_name.as_ref().unwrap_or(&unknown)));
+            nc::mvwaddstr(self.now_playing,
+                          2,
+                          1,
+                          &format!("on {}", track.album_name.as_ref().unwrap_or(&unknown)));
+
+            if let Some((current, total)) = state.progress() {
+                let progress = self.progress(current, total, (cols - 2).max(0) as usize);
+                nc::mvwaddstr(self.now_playing, 3, 1, &progress);
+            }
+        }
+
+        nc::mvwhline(self.now_playing, NOW_PLAYING_HEIGHT - 1, 0, nc::ACS_HLINE(), cols);
+        nc::wnoutrefresh(self.now_playing);
+    }
+
+    /// Returns the progress line for the given elapsed and total seconds, at
+    /// most `width` characters long.
+    fn progress(&self, current: i64, total: i64, width: usize) -> String {
+        let current = current.max(0).min(total.max(0));
+        let remaining = total.max(0) - current;
+        let (left, right) = match self.time_format {
+            TimeFormat::Elapsed => (time(current), time(total)),
+            TimeFormat::Remaining => (format!("-{}", time(remaining)), time(total)),
+            TimeFormat::Both => (time(current), format!("-{}", time(remaining))),
+        };
+
+        // The bar needs the times, two spaces, two brackets, and some room.
+        let text_width = left.len() + right.len() + 4;
+        if !self.progress_bar || width < text_width + MIN_BAR_WIDTH {
+            return format!("{}/{}", left, right);
+        }
+
+        let bar_width = width - text_width;
+        let filled = if total > 0 {
+            (bar_width as i64 * current / total) as usize
+        } else {
+            0
+        };
+        let mut bar = String::with_capacity(bar_width);
+        for i in 0..bar_width {
+            bar.push(if i < filled {
+                         '='
+                     } else if i == filled {
+                         '>'
+                     } else {
+                         ' '
+                     });
+        }
+        format!("{} [{}] {}", left, bar, right)
+    }
+}
+
+/// Formats the given seconds as `mm:ss`.
+fn time(seconds: i64) -> String {
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+impl Drop for Layout {
+    fn drop(&mut self) {
+        for window in &[self.header, self.now_playing, self.history, self.status] {
+            nc::delwin(*window);
+        }
+    }
+}
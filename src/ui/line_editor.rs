@@ -0,0 +1,290 @@
+//! Single line of input, edited in place on a window.
+//!
+//! The cursor is moved with the left and right arrows (or `C-b` / `C-f`) and home
+//! / end (or `C-a` / `C-e`). Backspace and delete (or `C-d`) remove the character
+//! before or under the cursor, `C-u`, `C-k` and `C-w` kill the text before the
+//! cursor, after it, or the word before it, and `C-y` yanks the last killed text
+//! back. The up and down arrows (or `C-p` / `C-n`) browse the lines entered
+//! before, tab cycles through the completions of the line, enter accepts it and
+//! escape cancels it.
+
+use libc;
+use ncurses as nc;
+
+use std::char;
+
+extern "C" {
+    fn wcwidth(c: libc::wchar_t) -> libc::c_int;
+}
+
+/// Number of lines kept in the history of an editor.
+const HISTORY_LENGTH: usize = 100;
+
+const CTRL_A: i32 = 1;
+const CTRL_B: i32 = 2;
+const CTRL_D: i32 = 4;
+const CTRL_E: i32 = 5;
+const CTRL_F: i32 = 6;
+const CTRL_K: i32 = 11;
+const CTRL_N: i32 = 14;
+const CTRL_P: i32 = 16;
+const CTRL_U: i32 = 21;
+const CTRL_W: i32 = 23;
+const CTRL_Y: i32 = 25;
+
+/// Line being edited, along with the lines entered before.
+pub struct LineEditor {
+    chars: Vec<char>,
+    cursor: usize,
+
+    // First character shown, when the line is wider than the window.
+    offset: usize,
+
+    // Masked editors show an asterisk for each character, and don't keep a
+    // history.
+    masked: bool,
+
+    // Lines entered before, and the one shown (`history.len()` for the line
+    // being typed).
+    history: Vec<String>,
+    position: usize,
+
+    // Text removed by the last kill.
+    killed: Vec<char>,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        LineEditor {
+            chars: Vec::new(),
+            cursor: 0,
+            offset: 0,
+            masked: false,
+            history: Vec::new(),
+            position: 0,
+            killed: Vec::new(),
+        }
+    }
+
+    /// Creates an editor for secrets, such as passwords.
+    pub fn masked() -> Self {
+        let mut editor = Self::new();
+        editor.masked = true;
+        editor
+    }
+
+    /// Reads a line at the cursor position of the given window. Returns `None`
+    /// when escape is pressed.
+    pub fn read(&mut self, window: nc::WINDOW) -> Option<String> {
+        self.read_with(window, |_| Vec::new())
+    }
+
+    /// Reads a line like [read](#method.read), where tab cycles through the
+    /// completions given by `complete` for the line typed before the first tab.
+    pub fn read_with<F>(&mut self, window: nc::WINDOW, mut complete: F) -> Option<String>
+        where F: FnMut(&str) -> Vec<String>
+    {
+        nc::noecho();
+        nc::keypad(window, true);
+
+        let mut y = 0;
+        let mut x = 0;
+        nc::getyx(window, &mut y, &mut x);
+
+        self.chars.clear();
+        self.cursor = 0;
+        self.offset = 0;
+        self.position = self.history.len();
+        // Line being typed, while browsing the history.
+        let mut typed = Vec::new();
+        // Completions and the one shown, until another key than tab is pressed.
+        let mut completions: Option<(Vec<String>, usize)> = None;
+
+        let result = loop {
+            self.draw(window, y, x);
+
+            let ch = match nc::wget_wch(window) {
+                Some(nc::WchResult::KeyCode(code)) => code,
+                Some(nc::WchResult::Char(code)) => {
+                    match char::from_u32(code) {
+                        // Printable characters are inserted as they are, the
+                        // others are handled like keys.
+                        Some(c) if !c.is_control() => {
+                            completions = None;
+                            self.insert(&[c]);
+                            continue;
+                        }
+                        _ => code as i32,
+                    }
+                }
+                None => continue,
+            };
+            if ch != '\t' as i32 {
+                completions = None;
+            }
+            match ch {
+                nc::KEY_ENTER | 10 | 13 => break Some(self.line()),
+                27 => break None,
+                nc::KEY_LEFT | CTRL_B => self.cursor = self.cursor.saturating_sub(1),
+                nc::KEY_RIGHT | CTRL_F => self.cursor = (self.cursor + 1).min(self.chars.len()),
+                nc::KEY_HOME | CTRL_A => self.cursor = 0,
+                nc::KEY_END | CTRL_E => self.cursor = self.chars.len(),
+                nc::KEY_BACKSPACE | 8 | 127 => {
+                    if self.cursor > 0 {
+                        self.cursor -= 1;
+                        self.chars.remove(self.cursor);
+                    }
+                }
+                nc::KEY_DC | CTRL_D => {
+                    if self.cursor < self.chars.len() {
+                        self.chars.remove(self.cursor);
+                    }
+                }
+                CTRL_U => {
+                    let end = self.cursor;
+                    self.kill(0, end);
+                }
+                CTRL_K => {
+                    let (start, end) = (self.cursor, self.chars.len());
+                    self.kill(start, end);
+                }
+                CTRL_W => {
+                    let end = self.cursor;
+                    let mut start = end;
+                    while start > 0 && self.chars[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    while start > 0 && !self.chars[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    self.kill(start, end);
+                }
+                CTRL_Y => {
+                    let killed = self.killed.clone();
+                    self.insert(&killed);
+                }
+                nc::KEY_UP | CTRL_P if !self.masked && self.position > 0 => {
+                    if self.position == self.history.len() {
+                        typed = self.chars.clone();
+                    }
+                    self.position -= 1;
+                    let line = self.history[self.position].chars().collect();
+                    self.set_line(line);
+                }
+                nc::KEY_DOWN | CTRL_N if !self.masked && self.position < self.history.len() => {
+                    self.position += 1;
+                    let line = match self.history.get(self.position) {
+                        Some(line) => line.chars().collect(),
+                        None => typed.clone(),
+                    };
+                    self.set_line(line);
+                }
+                9 => {
+                    let (candidates, index) = match completions.take() {
+                        Some((candidates, index)) => {
+                            let next = (index + 1) % candidates.len();
+                            (candidates, next)
+                        }
+                        None => (complete(&self.line()), 0),
+                    };
+                    if let Some(candidate) = candidates.get(index) {
+                        self.set_line(candidate.chars().collect());
+                    }
+                    if !candidates.is_empty() {
+                        completions = Some((candidates, index));
+                    }
+                }
+                _ => (),
+            }
+        };
+
+        nc::keypad(window, false);
+        if let Some(ref line) = result {
+            self.remember(line);
+        }
+        result
+    }
+
+    fn line(&self) -> String {
+        self.chars.iter().cloned().collect()
+    }
+
+    /// Replaces the line, with the cursor at its end.
+    fn set_line(&mut self, chars: Vec<char>) {
+        self.cursor = chars.len();
+        self.chars = chars;
+    }
+
+    fn insert(&mut self, chars: &[char]) {
+        for &c in chars {
+            self.chars.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    /// Removes the characters from `start` to `end`, keeping them to be yanked.
+    fn kill(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.killed = self.chars.drain(start..end).collect();
+            self.cursor = start;
+        }
+    }
+
+    /// Adds the given line to the history, unless it's empty, masked or the
+    /// same as the last one.
+    fn remember(&mut self, line: &str) {
+        if self.masked || line.trim().is_empty() ||
+           self.history.last().map(|last| last.as_str()) == Some(line) {
+            return;
+        }
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.remove(0);
+        }
+        self.history.push(line.to_owned());
+    }
+
+    /// Draws the line from the given position to the end of the window, scrolled
+    /// so the cursor is visible. Positions are counted in columns, since some
+    /// characters take two of them.
+    fn draw(&mut self, window: nc::WINDOW, y: i32, x: i32) {
+        let mut lines = 0;
+        let mut cols = 0;
+        nc::getmaxyx(window, &mut lines, &mut cols);
+        // The last column is kept for the cursor at the end of the line.
+        let width = (cols - x - 1).max(1) as usize;
+
+        let masked = self.masked;
+        let shown: Vec<char> =
+            self.chars.iter().map(|&c| if masked { '*' } else { c }).collect();
+        let widths: Vec<usize> = shown.iter().map(|&c| column_width(c)).collect();
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        }
+        while widths[self.offset..self.cursor].iter().sum::<usize>() >= width {
+            self.offset += 1;
+        }
+
+        let mut used = 0;
+        let text: String = shown[self.offset..]
+            .iter()
+            .zip(&widths[self.offset..])
+            .take_while(|&(_, &w)| {
+                used += w;
+                used <= width
+            })
+            .map(|(&c, _)| c)
+            .collect();
+        let cursor: usize = widths[self.offset..self.cursor].iter().sum();
+        nc::wmove(window, y, x);
+        nc::wclrtoeol(window);
+        nc::waddstr(window, &text);
+        nc::wmove(window, y, x + cursor as i32);
+        nc::wrefresh(window);
+    }
+}
+
+/// Returns the number of columns taken by the given character on the terminal.
+fn column_width(c: char) -> usize {
+    let width = unsafe { wcwidth(c as libc::wchar_t) };
+    if width < 0 { 1 } else { width as usize }
+}
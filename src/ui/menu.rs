@@ -0,0 +1,228 @@
+//! Scrollable list of items to choose from, drawn in the history pane.
+//!
+//! The list is moved through with the arrow keys (or `j` / `k`), page up / down
+//! and home / end (or `g` / `G`). `/` starts filtering the items: what is typed
+//! next keeps only the items containing it, until enter chooses the selected item
+//! or escape clears the filter.
+
+use ncurses as nc;
+
+/// Result of a key given to a [Menu](struct.Menu.html).
+pub enum MenuEvent {
+    /// The key was used by the menu.
+    None,
+    /// The item with the given index was chosen.
+    Choose(usize),
+    /// The menu was closed without choosing an item.
+    Cancel,
+    /// The key isn't used by the menu.
+    Key(i32),
+}
+
+/// List of items, some of them hidden by the filter, with one of them selected.
+pub struct Menu {
+    title: String,
+    items: Vec<String>,
+
+    // Item marked as the current one, if any.
+    marked: Option<usize>,
+
+    // Items matching the filter, and the selected and first visible ones (as
+    // indices into `matches`).
+    filter: String,
+    filtering: bool,
+    matches: Vec<usize>,
+    selected: usize,
+    offset: usize,
+
+    // Visible items in the last draw, to move by pages.
+    page: usize,
+}
+
+impl Menu {
+    /// Creates a menu with the given title and items, the first one selected.
+    pub fn new(title: &str, items: Vec<String>) -> Self {
+        let mut menu = Menu {
+            title: title.to_owned(),
+            items: Vec::new(),
+            marked: None,
+            filter: String::new(),
+            filtering: false,
+            matches: Vec::new(),
+            selected: 0,
+            offset: 0,
+            page: 1,
+        };
+        menu.set_items(items);
+        menu
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_owned();
+    }
+
+    /// Replaces the items, keeping the filter. The first item is selected.
+    pub fn set_items(&mut self, items: Vec<String>) {
+        self.items = items;
+        self.marked = None;
+        self.update_matches();
+    }
+
+    /// Marks the item with the given index as the current one.
+    pub fn set_marked(&mut self, index: Option<usize>) {
+        self.marked = index;
+    }
+
+    /// Selects the item with the given index, if it isn't filtered out.
+    pub fn select(&mut self, index: usize) {
+        if let Some(position) = self.matches.iter().position(|&i| i == index) {
+            self.selected = position;
+        }
+    }
+
+    /// Returns the index of the selected item, if any item matches the filter.
+    pub fn selected(&self) -> Option<usize> {
+        self.matches.get(self.selected).cloned()
+    }
+
+    /// Handles the given key.
+    pub fn key(&mut self, ch: i32) -> MenuEvent {
+        match ch {
+            nc::KEY_UP => self.move_by(-1),
+            nc::KEY_DOWN => self.move_by(1),
+            nc::KEY_PPAGE => {
+                let page = self.page as isize;
+                self.move_by(-page);
+            }
+            nc::KEY_NPAGE => {
+                let page = self.page as isize;
+                self.move_by(page);
+            }
+            nc::KEY_HOME => self.selected = 0,
+            nc::KEY_END => self.selected = self.matches.len().saturating_sub(1),
+            nc::KEY_ENTER | 10 | 13 => {
+                return match self.selected() {
+                           Some(index) => MenuEvent::Choose(index),
+                           None => MenuEvent::None,
+                       }
+            }
+            // Escape.
+            27 => {
+                if self.filtering || !self.filter.is_empty() {
+                    self.filtering = false;
+                    self.filter.clear();
+                    self.update_matches();
+                } else {
+                    return MenuEvent::Cancel;
+                }
+            }
+            _ if self.filtering => {
+                match ch {
+                    nc::KEY_BACKSPACE | 8 | 127 => {
+                        if self.filter.pop().is_none() {
+                            self.filtering = false;
+                        }
+                        self.update_matches();
+                    }
+                    ch if ch >= 32 && ch < 127 => {
+                        self.filter.push(ch as u8 as char);
+                        self.update_matches();
+                    }
+                    _ => return MenuEvent::Key(ch),
+                }
+            }
+            _ if ch < 0 || ch > 255 => return MenuEvent::Key(ch),
+            _ => {
+                match ch as u8 as char {
+                    'k' => self.move_by(-1),
+                    'j' => self.move_by(1),
+                    'g' => self.selected = 0,
+                    'G' => self.selected = self.matches.len().saturating_sub(1),
+                    '/' => self.filtering = true,
+                    'q' => return MenuEvent::Cancel,
+                    _ => return MenuEvent::Key(ch),
+                }
+            }
+        }
+        MenuEvent::None
+    }
+
+    /// Draws the menu on the whole given window.
+    pub fn draw(&mut self, window: nc::WINDOW) {
+        let mut lines = 0;
+        let mut cols = 0;
+        nc::getmaxyx(window, &mut lines, &mut cols);
+        // The last column is left empty, so long items don't wrap.
+        let width = (cols - 1).max(0);
+        nc::werase(window);
+
+        // The title and the filter take a line each.
+        self.page = (lines - 2).max(1) as usize;
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + self.page {
+            self.offset = self.selected + 1 - self.page;
+        }
+
+        nc::wattron(window, nc::A_BOLD());
+        nc::mvwaddnstr(window, 0, 0, &self.title, width);
+        nc::wattroff(window, nc::A_BOLD());
+
+        // Shows the visible items when they don't fit in one page.
+        if self.matches.len() > self.page {
+            let last = (self.offset + self.page).min(self.matches.len());
+            let position = format!(" {}-{} of {}", self.offset + 1, last, self.matches.len());
+            let x = width - position.len() as i32;
+            if x > 0 {
+                nc::mvwaddstr(window, 0, x, &position);
+            }
+        }
+
+        if self.matches.is_empty() {
+            nc::mvwaddnstr(window, 1, 0, "  (no matches)", width);
+        }
+        let visible = self.matches.iter().enumerate().skip(self.offset).take(self.page);
+        for (row, (position, &index)) in visible.enumerate() {
+            let prefix = if self.marked == Some(index) { "* " } else { "  " };
+            if position == self.selected {
+                nc::wattron(window, nc::A_REVERSE());
+            }
+            nc::mvwaddnstr(window,
+                           row as i32 + 1,
+                           0,
+                           &format!("{}{}", prefix, self.items[index]),
+                           width);
+            nc::wattroff(window, nc::A_REVERSE());
+        }
+
+        if self.filtering || !self.filter.is_empty() {
+            nc::mvwaddnstr(window, lines - 1, 0, &format!("/{}", self.filter), width);
+        }
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let last = self.matches.len().saturating_sub(1) as isize;
+        let selected = self.selected as isize + delta;
+        self.selected = if selected < 0 {
+            0
+        } else if selected > last {
+            last as usize
+        } else {
+            selected as usize
+        };
+    }
+
+    /// Updates the items matching the filter (ignoring case), and selects the
+    /// first one.
+    fn update_matches(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.matches = self.items
+            .iter()
+            .enumerate()
+            .filter(|&(_, item)| item.to_lowercase().contains(&filter))
+            .map(|(index, _)| index)
+            .collect();
+        self.selected = 0;
+        self.offset = 0;
+    }
+}